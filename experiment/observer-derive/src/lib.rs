@@ -0,0 +1,233 @@
+//! Derive macro for the timeline requests of `leaky-snowflake-observer`.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, Type};
+
+/// Derives `api::Endpoint` and `api::TimelineRequest` for a struct of request parameters, which
+/// should derive `oauth::Request` as well.
+///
+/// The struct takes the following attributes:
+///
+/// - `#[timeline(path = "/lists/statuses.json")]`: the path of the endpoint (required)
+/// - `#[timeline(item = Tweet)]`: the type of the items of the timeline (required)
+/// - `#[timeline(version = "1.1")]`: the API version, prepended to the path
+/// - `#[timeline(host = "api.twitter.com")]`: the host of the API (default: `api::HOST`)
+/// - `#[timeline(envelope = "data")]`: the field of the response object that holds the items,
+///   if the response is not a bare array
+/// - `#[timeline(page = DataPage<Tweet>)]`: the type of the whole response body, implementing
+///   `api::Envelope` (default: `Vec` of the items)
+/// - `#[timeline(oldest_first)]`: the endpoint returns the oldest items above `since_id`
+///
+/// and its fields take the following ones:
+///
+/// - `#[timeline(since_id)]`: the `since_id` parameter, of type `Option<u64>` (required)
+/// - `#[timeline(max_id)]`: the `max_id` parameter, of type `Option<u64>`, which makes the struct
+///   implement `api::PagedRequest` and `api::RangeRequest` as well
+/// - `#[timeline(cursor)]`: the cursor parameter, of type `Option<api::Cursor>`
+/// - `#[timeline(limit)]`: the maximum number of items in a response, e.g. `count`
+#[proc_macro_derive(TimelineRequest, attributes(timeline))]
+pub fn derive_timeline_request(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct Container {
+    path: Option<LitStr>,
+    item: Option<Type>,
+    version: Option<LitStr>,
+    host: Option<LitStr>,
+    envelope: Option<LitStr>,
+    page: Option<Type>,
+    oldest_first: bool,
+}
+
+#[derive(Default)]
+struct Params<'a> {
+    since_id: Option<&'a Ident>,
+    max_id: Option<&'a Ident>,
+    cursor: Option<&'a Ident>,
+    limit: Option<&'a Ident>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(input)?;
+    let Data::Struct(ref data) = input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "`TimelineRequest` can only be derived for structs",
+        ));
+    };
+    let Fields::Named(ref fields) = data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "`TimelineRequest` requires named fields",
+        ));
+    };
+    let params = Params::parse(fields.named.iter())?;
+
+    let krate = quote! { ::leaky_snowflake_observer };
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Some(ref path) = container.path else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "missing `#[timeline(path = \"..\")]` attribute",
+        ));
+    };
+    let path = match container.version {
+        Some(ref version) => format!("/{}{}", version.value(), path.value()),
+        None => path.value(),
+    };
+    let host = container
+        .host
+        .as_ref()
+        .map(|host| quote! { const HOST: &'static str = #host; });
+    let envelope = container.envelope.as_ref().map(|envelope| {
+        quote! { const ENVELOPE: ::core::option::Option<&'static str> = ::core::option::Option::Some(#envelope); }
+    });
+
+    let Some(ref item) = container.item else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "missing `#[timeline(item = ..)]` attribute",
+        ));
+    };
+    let oldest_first = container
+        .oldest_first
+        .then(|| quote! { const OLDEST_FIRST: bool = true; });
+    let Some(since_id) = params.since_id else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "missing a field with `#[timeline(since_id)]` attribute",
+        ));
+    };
+    let limit = params.limit.map(|limit| {
+        quote! {
+            fn limit(&self) -> usize {
+                self.#limit as usize
+            }
+        }
+    });
+
+    let paged = match params.max_id {
+        Some(max_id) => {
+            let page = match container.page {
+                Some(ref page) => quote! { #page },
+                None => quote! { ::std::vec::Vec<#item> },
+            };
+            let cursor = params.cursor.map(|cursor| {
+                quote! {
+                    fn set_cursor(&mut self, cursor: ::core::option::Option<#krate::api::Cursor>) {
+                        self.#cursor = cursor;
+                    }
+                }
+            });
+            Some(quote! {
+                impl #impl_generics #krate::api::PagedRequest for #name #ty_generics #where_clause {
+                    type Envelope = #page;
+
+                    #cursor
+                }
+
+                impl #impl_generics #krate::api::RangeRequest for #name #ty_generics #where_clause {
+                    fn set_max_id(&mut self, max_id: ::core::option::Option<u64>) {
+                        self.#max_id = max_id;
+                    }
+                }
+            })
+        }
+        None if params.cursor.is_some() || container.page.is_some() => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`cursor` and `page` require a field with `#[timeline(max_id)]` attribute",
+            ));
+        }
+        None => None,
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::api::Endpoint for #name #ty_generics #where_clause {
+            #host
+            const PATH: &'static str = #path;
+            #envelope
+        }
+
+        impl #impl_generics #krate::api::TimelineRequest for #name #ty_generics #where_clause {
+            type Item = #item;
+
+            #oldest_first
+
+            #limit
+
+            fn set_since_id(&mut self, since_id: ::core::option::Option<u64>) {
+                self.#since_id = since_id;
+            }
+        }
+
+        #paged
+    })
+}
+
+impl Container {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut ret = Container::default();
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("timeline")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("path") {
+                    ret.path = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("item") {
+                    ret.item = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("version") {
+                    ret.version = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("host") {
+                    ret.host = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("envelope") {
+                    ret.envelope = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("page") {
+                    ret.page = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("oldest_first") {
+                    ret.oldest_first = true;
+                } else {
+                    return Err(meta.error("unknown `timeline` attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(ret)
+    }
+}
+
+impl<'a> Params<'a> {
+    fn parse(fields: impl Iterator<Item = &'a syn::Field>) -> syn::Result<Self> {
+        let mut ret = Params::default();
+        for field in fields {
+            let name = field.ident.as_ref().unwrap();
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("timeline")) {
+                attr.parse_nested_meta(|meta| {
+                    let duplicate = if meta.path.is_ident("since_id") {
+                        ret.since_id.replace(name).is_some()
+                    } else if meta.path.is_ident("max_id") {
+                        ret.max_id.replace(name).is_some()
+                    } else if meta.path.is_ident("cursor") {
+                        ret.cursor.replace(name).is_some()
+                    } else if meta.path.is_ident("limit") {
+                        ret.limit.replace(name).is_some()
+                    } else {
+                        return Err(meta.error("unknown `timeline` attribute"));
+                    };
+                    if duplicate {
+                        return Err(meta.error("duplicate `timeline` attribute"));
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,349 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{stdout, BufRead, Write};
+use std::process::ExitCode;
+
+use getopts::Options;
+use serde::{Deserialize, Serialize};
+
+use leaky_snowflake_observer::snowflake::Layout;
+
+use crate::capture;
+use crate::decode;
+use crate::util;
+
+/// A leak report printed by `run::Observer::poll_timeline`, of which only the fields needed for
+/// the analysis are read.
+#[derive(Debug, Deserialize)]
+struct Record {
+    k_ms: u64,
+    nth: u64,
+    previous: Previous,
+    latest: Latest,
+    /// Absent in the records from older versions, which report the leaked statuses only as part
+    /// of the timelines.
+    #[serde(default)]
+    leaks: Option<Vec<Leak>>,
+    magic: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Previous {
+    retrieved_ms: u64,
+    latest_id: u64,
+    #[serde(default)]
+    statuses: Vec<Status>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Latest {
+    retrieved_ms: u64,
+    #[serde(default)]
+    statuses: Vec<Status>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Leak {
+    id: u64,
+    missed_polls: usize,
+    delay_ms: u64,
+    /// Absent in the records from older versions.
+    #[serde(default)]
+    kind: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Analysis {
+    records: u64,
+    /// Number of the records in which the leaked status was returned with the naive `since_id`.
+    magic: u64,
+    leaks: Vec<Analyzed>,
+    /// The lowest `k` value that would have caught all the leaked statuses.
+    min_k_ms: Option<u64>,
+    workers: Vec<WorkerLeaks>,
+}
+
+#[derive(Debug, Serialize)]
+struct Analyzed {
+    nth: u64,
+    id: u64,
+    timestamp: String,
+    unix_ms: u64,
+    datacenter_id: u64,
+    worker_id: u64,
+    sequence: u64,
+    /// Timestamp of the leaked ID relative to that of the `latest_id` of the previous poll.
+    latest_delta_ms: i64,
+    /// Timestamp of the leaked ID relative to the time the previous poll was initiated.
+    retrieved_delta_ms: i64,
+    /// The lowest `k` value with which the `since_id` following the previous poll would have
+    /// been lower than the leaked ID.
+    min_k_ms: u64,
+    /// Whether the record's own `k` value would have caught the leaked status.
+    caught: bool,
+    missed_polls: usize,
+    delay_ms: u64,
+    kind: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkerLeaks {
+    datacenter_id: u64,
+    worker_id: u64,
+    leaks: u64,
+    max_min_k_ms: u64,
+}
+
+pub fn main(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    decode::layout_opts(&mut opts);
+    opts.optopt("", "format", "output format (default: text)", "text|json");
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let layout = decode::layout(&matches)?;
+    let json = match matches.opt_str("format").as_deref() {
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(format) => anyhow::bail!("unknown format: {}", format),
+    };
+
+    let mut records = Vec::new();
+    if matches.free.is_empty() {
+        read(capture::open("-")?, &mut records)?;
+    }
+    for path in &matches.free {
+        read(capture::open(path)?, &mut records)?;
+    }
+    if records.is_empty() {
+        println!("{}: no leak record found", program);
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let analysis = analyze(&records, &layout);
+
+    let mut stdout = stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &analysis)?;
+        writeln!(stdout)?;
+    } else {
+        print_text(&mut stdout, &analysis)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} analyze [OPTIONS..] [FILE..]", program);
+    print!("{}", opts.usage(&brief));
+    println!("\nReads the leak records from the standard input if no FILE is given.");
+}
+
+/// Reads the leak records from the output of the observer, skipping the log lines and the other
+/// records, e.g. those of `--compare`.
+fn read(reader: impl BufRead, records: &mut Vec<Record>) -> anyhow::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim_start().starts_with('{') {
+            continue;
+        }
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if value.get("previous").is_none() {
+            continue;
+        }
+        let mut record: Record = serde_json::from_value(value)?;
+        if record.leaks.is_none() {
+            record.leaks = Some(record.derive_leaks());
+        }
+        records.push(record);
+    }
+    Ok(())
+}
+
+impl Record {
+    /// Returns the statuses of the latest timeline at or below the `latest_id` of the previous
+    /// one that are missing from the previous one, as the older versions detected the leaks.
+    fn derive_leaks(&self) -> Vec<Leak> {
+        self.latest
+            .statuses
+            .iter()
+            .filter(|t| t.id <= self.previous.latest_id)
+            .filter(|t| !self.previous.statuses.iter().any(|u| u.id == t.id))
+            .map(|t| Leak {
+                id: t.id,
+                missed_polls: 1,
+                delay_ms: self
+                    .latest
+                    .retrieved_ms
+                    .saturating_sub(self.previous.retrieved_ms),
+                kind: None,
+            })
+            .collect()
+    }
+}
+
+fn analyze(records: &[Record], layout: &Layout) -> Analysis {
+    let mut ret = Analysis::default();
+    let mut workers: BTreeMap<(u64, u64), WorkerLeaks> = BTreeMap::new();
+
+    for record in records {
+        ret.records += 1;
+        if record.magic == Some(true) {
+            ret.magic += 1;
+        }
+
+        let latest_ms = layout.timestamp_ms(record.previous.latest_id);
+        let retrieved_ms = record.previous.retrieved_ms;
+        for leak in record.leaks.iter().flatten() {
+            let unix_ms = layout.timestamp_ms(leak.id);
+            // The adjusted `since_id` is below the leaked ID if and only if `k` covers the gap
+            // between the leaked timestamp and both of the bounds in `SinceIdPolicy::since_id`.
+            let min_k_ms = retrieved_ms.max(latest_ms).saturating_sub(unix_ms);
+            let analyzed = Analyzed {
+                nth: record.nth,
+                id: leak.id,
+                timestamp: util::format_unix_ms(unix_ms),
+                unix_ms,
+                datacenter_id: layout.datacenter_id(leak.id),
+                worker_id: layout.worker_id(leak.id),
+                sequence: layout.sequence(leak.id),
+                latest_delta_ms: unix_ms as i64 - latest_ms as i64,
+                retrieved_delta_ms: unix_ms as i64 - retrieved_ms as i64,
+                min_k_ms,
+                caught: record.k_ms >= min_k_ms,
+                missed_polls: leak.missed_polls,
+                delay_ms: leak.delay_ms,
+                kind: leak.kind.clone(),
+            };
+
+            ret.min_k_ms = ret.min_k_ms.max(Some(min_k_ms));
+            let worker = workers
+                .entry((analyzed.datacenter_id, analyzed.worker_id))
+                .or_insert(WorkerLeaks {
+                    datacenter_id: analyzed.datacenter_id,
+                    worker_id: analyzed.worker_id,
+                    leaks: 0,
+                    max_min_k_ms: 0,
+                });
+            worker.leaks += 1;
+            worker.max_min_k_ms = worker.max_min_k_ms.max(min_k_ms);
+
+            ret.leaks.push(analyzed);
+        }
+    }
+
+    ret.workers = workers.into_values().collect();
+    // Most leaking workers first.
+    ret.workers.sort_by_key(|w| Reverse(w.leaks));
+
+    ret
+}
+
+fn print_text(mut w: impl Write, analysis: &Analysis) -> anyhow::Result<()> {
+    writeln!(
+        w,
+        "{} record(s), {} leaked status(es), {} with the \"magic\"",
+        analysis.records,
+        analysis.leaks.len(),
+        analysis.magic,
+    )?;
+    if let Some(min_k_ms) = analysis.min_k_ms {
+        writeln!(w, "k needed to catch all the leaks: {} ms", min_k_ms)?;
+    }
+
+    writeln!(w, "\nLeaks:")?;
+    writeln!(
+        w,
+        "{:>8} {:>20} {:>24} {:>4} {:>6} {:>4} {:>10} {:>10} {:>8} {:>6} {:>8} {:>8}",
+        "nth",
+        "id",
+        "timestamp",
+        "dc",
+        "worker",
+        "seq",
+        "vs_latest",
+        "vs_retr",
+        "min_k",
+        "caught",
+        "delay",
+        "kind",
+    )?;
+    for leak in &analysis.leaks {
+        writeln!(
+            w,
+            "{:>8} {:>20} {:>24} {:>4} {:>6} {:>4} {:>10} {:>10} {:>8} {:>6} {:>8} {:>8}",
+            leak.nth,
+            leak.id,
+            leak.timestamp,
+            leak.datacenter_id,
+            leak.worker_id,
+            leak.sequence,
+            leak.latest_delta_ms,
+            leak.retrieved_delta_ms,
+            leak.min_k_ms,
+            leak.caught,
+            leak.delay_ms,
+            leak.kind.as_deref().unwrap_or("-"),
+        )?;
+    }
+
+    writeln!(w, "\nBy worker:")?;
+    writeln!(
+        w,
+        "{:>4} {:>6} {:>6} {:>8}",
+        "dc", "worker", "leaks", "min_k"
+    )?;
+    for worker in &analysis.workers {
+        writeln!(
+            w,
+            "{:>4} {:>6} {:>6} {:>8}",
+            worker.datacenter_id, worker.worker_id, worker.leaks, worker.max_min_k_ms,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_records() {
+        let input = r#"2023-01-01T00:00:00.000000Z  INFO leaky_snowflake_observer: Observed a leaked status
+{"k_ms":1000,"start_ms":0,"nth":3,"previous":{"retrieved_ms":1000,"latest_id":30,"statuses":[{"id":30},{"id":10}]},"latest":{"retrieved_ms":2000,"statuses":[{"id":40},{"id":30},{"id":20}]},"magic":false}
+{"k_ms":1000,"start_ms":0,"nth":5,"request":{},"previous":{"retrieved_ms":3000,"latest_id":40,"statuses":[]},"latest":{"retrieved_ms":4000,"statuses":[]},"leaks":[{"id":35,"missed_polls":2,"delay_ms":2000,"kind":"original"}],"magic":null}
+{"type":"summary","nth":60,"naive_lost":0,"adjusted_lost":0}
+"#;
+        let mut records = Vec::new();
+        read(input.as_bytes(), &mut records).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let leaks = records[0].leaks.as_ref().unwrap();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].id, 20);
+        assert_eq!(leaks[0].delay_ms, 1000);
+
+        let leaks = records[1].leaks.as_ref().unwrap();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!((leaks[0].id, leaks[0].missed_polls), (35, 2));
+    }
+}
@@ -1,86 +1,76 @@
-macro_rules! def_timelines {
+macro_rules! impl_request {
+    ($Name:ident, $path:literal) => {
+        impl_request!($Name, $path, GET);
+    };
+    ($Name:ident, $path:literal, $method:ident) => {
+        impl $crate::api::Endpoint for $Name {
+            const PATH: &'static str = $path;
+            const METHOD: hyper::Method = hyper::Method::$method;
+        }
+    };
+}
+
+macro_rules! def_requests {
     ($(
-        $path:literal;
+        $path:literal $(, $method:ident)?;
         $(#[$attr:meta])*
         $vis:vis struct $Name:ident {
-            $($ctor_arg:ident: $C:ty,)*
-            @since_id $since_id:ident: Option<u64>
-            $(, $param:ident: $P:ty = $param_default:expr)* $(,)?
+            $($(#[$ctor_attr:meta])* $ctor_arg:ident: $C:ty),*;
+            $($(#[$param_attr:meta])* $param:ident: $P:ty = $param_default:expr),* $(,)?
         }
     )*) => {$(
         $(#[$attr])*
         $vis struct $Name {
-            $($ctor_arg: $C,)*
-            $since_id: Option<u64>,
-            $($param: $P,)*
+            $($(#[$ctor_attr])* $ctor_arg: $C,)*
+            $($(#[$param_attr])* $param: $P,)*
         }
 
         impl $Name {
             pub fn new($($ctor_arg: $C),*) -> Self {
                 Self {
                     $($ctor_arg,)*
-                    $since_id: None,
                     $($param: $param_default,)*
                 }
             }
         }
 
-        impl $crate::api::TimelineRequest for $Name {
-            fn set_since_id(&mut self, since_id: Option<u64>) {
-                self.$since_id = since_id;
-            }
-
-            fn fetch<D>(
-                &self,
-                seed: D,
-                token: &$crate::api::Token,
-                request_sender: &mut hyper::client::conn::http2::SendRequest<
-                    http_body_util::Empty<bytes::Bytes>,
-                >,
-            ) -> $crate::api::ResponseFuture<D>
-            where
-                D: for<'de> serde::de::DeserializeSeed<'de>,
-            {
-                const ENDPOINT: &str = concat!("https://api.twitter.com", $path);
-                const PATH: &str = $path;
-
-                let response = Box::pin($crate::api::send_request(
-                    self,
-                    ENDPOINT,
-                    PATH,
-                    token,
-                    request_sender,
-                ));
-                let inner = $crate::api::response::Inner::Response { response };
-                $crate::api::ResponseFuture { inner, seed: Some(seed) }
-            }
-        }
+        impl_request!($Name, $path $(, $method)?);
     )*};
 }
 
+pub mod discord;
 pub mod lists;
+pub mod mastodon;
+pub mod statuses;
+pub mod stream;
+pub mod users;
 
 mod response;
 
 pub use self::response::ResponseFuture;
+pub use leaky_snowflake_observer_derive::TimelineRequest;
 
 use std::cmp::Ordering;
-use std::future::Future;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
 
 use bytes::Bytes;
 use http_body_util::Empty;
-use hyper::client::conn::http2::SendRequest;
 use hyper::header::{self, HeaderValue};
-use hyper::{Request, Response, Uri};
-use serde::{de::DeserializeSeed, Deserialize, Serialize};
+use hyper::{Method, StatusCode, Uri};
+use serde::de::{self, DeserializeOwned, DeserializeSeed};
+use serde::{Deserialize, Serialize};
 
+use crate::snowflake::Layout;
+use crate::transport::{SendFuture, Transport};
 use crate::util;
 
-pub const HOST: &'static str = "api.twitter.com";
+pub const HOST: &str = "api.twitter.com";
 
 const AUTHORITY: HeaderValue = HeaderValue::from_static(HOST);
 const GZIP: HeaderValue = HeaderValue::from_static("gzip");
 
+#[derive(Clone)]
 pub enum Token {
     UserContext(oauth::Token),
     AppOnly(HeaderValue),
@@ -89,24 +79,241 @@ pub enum Token {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Tweet {
     pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_str: Option<String>,
+    /// The creation time in the format of `Wed Oct 10 20:19:24 +0000 2018`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
     pub user: User,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retweeted_status: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quoted_status_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to_status_id: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct User {
     pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protected: Option<bool>,
+}
+
+/// A reference to another status embedded in a status.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Reference {
+    pub id: u64,
+}
+
+/// What a status is, as far as the ordering of its ID is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// A retweet, whose ID is minted at the time of the retweet rather than the original post.
+    Retweet,
+    Quote,
+    Reply,
+    Original,
+}
+
+/// An error returned when the API responds with a non-successful status code.
+#[derive(Debug)]
+pub struct StatusError(pub StatusCode);
+
+/// An error returned when the API responds with `429 Too Many Requests`.
+#[derive(Debug)]
+pub struct RateLimitError {
+    /// The time the rate limit resets at, in Unix seconds, if the API told us.
+    pub reset: Option<u64>,
 }
 
-pub trait TimelineRequest {
+pub trait Request {
+    /// Returns the host that the request is sent to.
+    fn host(&self) -> &str {
+        HOST
+    }
+
+    fn fetch<D>(&self, seed: D, token: &Token, transport: &mut dyn Transport) -> ResponseFuture<D>
+    where
+        D: for<'de> DeserializeSeed<'de>;
+}
+
+/// An endpoint of the Twitter API, whose parameters are given by `oauth::Request`.
+///
+/// Every `Endpoint` is a `Request`.
+pub trait Endpoint: oauth::Request {
+    const HOST: &'static str = HOST;
+    const PATH: &'static str;
+    const METHOD: Method = Method::GET;
+    /// The field of the response object that holds the payload, or `None` if the payload is
+    /// the response itself.
+    const ENVELOPE: Option<&'static str> = None;
+}
+
+pub trait TimelineRequest: Request {
+    type Item: TimelineItem;
+
+    /// Whether the requests return the oldest items above `since_id` rather than the newest
+    /// ones, so that a full response leaves out the newest items.
+    const OLDEST_FIRST: bool = false;
+
+    /// Returns the maximum number of items in a response, at which the response may have left
+    /// out some of the items.
+    fn limit(&self) -> usize {
+        200
+    }
+
     fn set_since_id(&mut self, since_id: Option<u64>);
-    fn fetch<D>(
+}
+
+/// A request for a collection that spans more than a single response, such as a timeline or the
+/// members of a List.
+pub trait PagedRequest: Request + Clone {
+    /// The response body of a page, holding the items along with the pagination metadata.
+    type Envelope: Envelope;
+
+    /// Sets the cursor of the page to be returned. The requests without cursors ignore it.
+    fn set_cursor(&mut self, cursor: Option<Cursor>) {
+        let _ = cursor;
+    }
+
+    /// Retrieves the page of the request as a whole `Envelope`.
+    fn fetch_envelope(
         &self,
-        seed: D,
         token: &Token,
-        request_sender: &mut SendRequest<Empty<Bytes>>,
-    ) -> ResponseFuture<D>
+        transport: &mut dyn Transport,
+    ) -> ResponseFuture<PhantomData<Self::Envelope>> {
+        self.fetch(PhantomData, token, transport).ignore_envelope()
+    }
+
+    /// Returns the request for the page at the cursor of `page`, or `None` if `page` has none.
+    fn next_page(&self, page: &Self::Envelope) -> Option<Self> {
+        let mut ret = self.clone();
+        ret.set_cursor(Some(page.next_cursor()?));
+        Some(ret)
+    }
+}
+
+/// A `TimelineRequest` whose range of IDs can be bounded from above as well, so that it can page
+/// through the timeline without cursors.
+pub trait RangeRequest: TimelineRequest + PagedRequest {
+    /// Sets the upper bound (inclusive) of the IDs to be returned.
+    fn set_max_id(&mut self, max_id: Option<u64>);
+}
+
+/// The response body of a page of a timeline or a collection.
+pub trait Envelope: DeserializeOwned {
+    type Item;
+
+    fn items(&self) -> &[Self::Item];
+
+    fn into_items(self) -> Vec<Self::Item>;
+
+    /// Returns the cursor of the following page, if the response tells.
+    fn next_cursor(&self) -> Option<Cursor> {
+        None
+    }
+}
+
+/// An opaque token designating a page of a collection.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Cursor(pub String);
+
+/// An item of a timeline, identified by a k-sorted ID whose upper bits hold its timestamp.
+pub trait TimelineItem: Debug + DeserializeOwned + Serialize {
+    /// The layout of the IDs.
+    const LAYOUT: Layout = Layout::TWITTER;
+
+    fn id(&self) -> u64;
+
+    /// Returns the ID of the author of the item, if the timeline tells.
+    fn author_id(&self) -> Option<u64> {
+        None
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Original
+    }
+
+    /// Returns whether the timestamp of the ID agrees with the creation time of the item
+    /// reported by the API, or `None` if the latter is unavailable.
+    fn is_created_at_consistent(&self) -> Option<bool> {
+        None
+    }
+
+    fn cmp_rev_id(&self, other: &Self) -> Ordering {
+        other.id().cmp(&self.id())
+    }
+}
+
+/// A page of the Twitter API v2, with the items in `data` and the cursor in `meta`.
+#[derive(Debug, Deserialize)]
+pub struct DataPage<T> {
+    #[serde(default = "Vec::new")]
+    pub data: Vec<T>,
+    #[serde(default)]
+    pub meta: Meta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Meta {
+    #[serde(default)]
+    pub next_token: Option<String>,
+}
+
+impl<E: Endpoint> Request for E {
+    fn host(&self) -> &str {
+        E::HOST
+    }
+
+    fn fetch<D>(&self, seed: D, token: &Token, transport: &mut dyn Transport) -> ResponseFuture<D>
     where
-        D: for<'de> DeserializeSeed<'de>;
+        D: for<'de> DeserializeSeed<'de>,
+    {
+        let (uri, authorization) = authorize(self, &E::METHOD, E::HOST, E::PATH, token);
+        let response = send_request(E::METHOD, E::HOST, uri, authorization, transport);
+        ResponseFuture {
+            inner: response::Inner::Response { response },
+            seed: Some(seed),
+            envelope: E::ENVELOPE,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Envelope for Vec<T> {
+    type Item = T;
+
+    fn items(&self) -> &[T] {
+        self
+    }
+
+    fn into_items(self) -> Vec<T> {
+        self
+    }
+}
+
+impl<T: DeserializeOwned> Envelope for DataPage<T> {
+    type Item = T;
+
+    fn items(&self) -> &[T] {
+        &self.data
+    }
+
+    fn into_items(self) -> Vec<T> {
+        self.data
+    }
+
+    fn next_cursor(&self) -> Option<Cursor> {
+        self.meta.next_token.clone().map(Cursor)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 impl Token {
@@ -115,6 +322,13 @@ impl Token {
             .ok()
             .map(Token::AppOnly)
     }
+
+    /// Creates a token from the token of a Discord bot.
+    pub fn from_bot(token: &str) -> Option<Self> {
+        HeaderValue::try_from(format!("Bot {}", token))
+            .ok()
+            .map(Token::AppOnly)
+    }
 }
 
 impl From<oauth::Token> for Token {
@@ -123,45 +337,222 @@ impl From<oauth::Token> for Token {
     }
 }
 
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bad status: {}", self.0)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Rate limit exceeded")?;
+        if let Some(reset) = self.reset {
+            write!(f, " until {}", reset)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RateLimitError {}
+
 impl Tweet {
-    pub fn cmp_rev_id(&self, other: &Self) -> Ordering {
-        other.id.cmp(&self.id)
+    /// Returns the `created_at` time in Unix milliseconds, truncated to the second.
+    pub fn created_at_ms(&self) -> Option<u64> {
+        self.created_at
+            .as_deref()
+            .and_then(util::parse_twitter_time)
     }
 }
 
-fn send_request<R>(
+impl TimelineItem for Tweet {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn author_id(&self) -> Option<u64> {
+        Some(self.user.id)
+    }
+
+    fn kind(&self) -> Kind {
+        if self.retweeted_status.is_some() {
+            Kind::Retweet
+        } else if self.quoted_status_id.is_some() {
+            Kind::Quote
+        } else if self.in_reply_to_status_id.is_some() {
+            Kind::Reply
+        } else {
+            Kind::Original
+        }
+    }
+
+    /// Returns whether the timestamp of the ID falls in the second of `created_at`.
+    fn is_created_at_consistent(&self) -> Option<bool> {
+        let created_at_ms = self.created_at_ms()?;
+        Some(util::sf_to_unix_ms(self.id) / 1000 == created_at_ms / 1000)
+    }
+}
+
+/// Retrieves the items of all the pages of the request by following the cursors.
+pub async fn fetch_pages<R: PagedRequest>(
+    request: &R,
+    token: &Token,
+    transport: &mut dyn Transport,
+) -> anyhow::Result<Vec<<R::Envelope as Envelope>::Item>> {
+    let mut request = request.clone();
+    let mut ret = Vec::new();
+    loop {
+        let page = request.fetch_envelope(token, transport).await?;
+        let next = request.next_page(&page);
+        ret.extend(page.into_items());
+        match next {
+            Some(next) => request = next,
+            None => return Ok(ret),
+        }
+    }
+}
+
+/// Retrieves the items with the IDs in the range of `(since_id, max_id]` by following the pages,
+/// e.g. to fill a gap in a timeline.
+///
+/// The pages are followed by their cursors if any, or otherwise beyond their items in the order
+/// of the responses.
+pub async fn fetch_range<R>(
+    request: &R,
+    since_id: Option<u64>,
+    max_id: Option<u64>,
+    token: &Token,
+    transport: &mut dyn Transport,
+) -> anyhow::Result<Vec<R::Item>>
+where
+    R: RangeRequest,
+    R::Envelope: Envelope<Item = R::Item>,
+{
+    let mut request = request.clone();
+    request.set_since_id(since_id);
+    request.set_max_id(max_id);
+    let mut ret = Vec::new();
+    loop {
+        let page = request.fetch_envelope(token, transport).await?;
+        let next = request.next_page(&page).or_else(|| {
+            let mut ret = request.clone();
+            let ids = page.items().iter().map(TimelineItem::id);
+            if R::OLDEST_FIRST {
+                ret.set_since_id(Some(ids.max()?));
+            } else {
+                ret.set_max_id(Some(ids.min()?.checked_sub(1)?));
+            }
+            Some(ret)
+        });
+        let items = page.into_items();
+        if items.is_empty() {
+            break;
+        }
+        // Not every endpoint takes both of the bounds at once (e.g. Discord's `after` and
+        // `before`), so the items are filtered here as well.
+        let in_range = |id: u64| since_id.is_none_or(|s| id > s) && max_id.is_none_or(|m| id <= m);
+        let beyond = items.iter().any(|t| {
+            if R::OLDEST_FIRST {
+                max_id.is_some_and(|m| t.id() > m)
+            } else {
+                since_id.is_some_and(|s| t.id() <= s)
+            }
+        });
+        ret.extend(items.into_iter().filter(|t| in_range(t.id())));
+        if beyond {
+            break;
+        }
+        match next {
+            Some(next) => request = next,
+            None => break,
+        }
+    }
+    Ok(ret)
+}
+
+/// Returns the URI of the request with the parameters in the query string and the value of its
+/// `Authorization` header.
+fn authorize<R>(
     request: &R,
-    endpoint: &str,
+    method: &Method,
+    host: &str,
     path: &str,
     token: &Token,
-    request_sender: &mut SendRequest<Empty<Bytes>>,
-) -> impl Future<Output = Result<hyper::Response<hyper::body::Incoming>, hyper::Error>>
+) -> (Uri, HeaderValue)
 where
     R: oauth::Request,
 {
+    let endpoint = &format!("https://{}{}", host, path);
     tracing::trace!(endpoint, "ep");
     let authorization = match *token {
+        // The parameters of `POST` requests are sent in the query string as well, which is
+        // signed in the same way as a form-encoded body.
+        Token::UserContext(ref token) if method == Method::POST => {
+            oauth::post(endpoint, request, token, oauth::HMAC_SHA1)
+                .try_into()
+                .unwrap()
+        }
         Token::UserContext(ref token) => oauth::get(endpoint, request, token, oauth::HMAC_SHA1)
             .try_into()
             .unwrap(),
         Token::AppOnly(ref authorization) => authorization.clone(),
     };
     let uri = Uri::try_from(oauth::to_query(path.to_owned(), request)).unwrap();
+    (uri, authorization)
+}
+
+fn send_request(
+    method: Method,
+    host: &str,
+    uri: Uri,
+    authorization: HeaderValue,
+    transport: &mut dyn Transport,
+) -> SendFuture {
+    let request = hyper::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::HOST, HeaderValue::try_from(host).unwrap())
+        .header(header::ACCEPT_ENCODING, GZIP)
+        .header(header::AUTHORIZATION, authorization)
+        .header(header::USER_AGENT, util::USER_AGENT)
+        .body(Empty::<Bytes>::new())
+        .unwrap();
+    transport.send(request)
+}
 
-    fn inner(
-        uri: Uri,
-        authorization: HeaderValue,
-        request_sender: &mut SendRequest<Empty<Bytes>>,
-    ) -> impl Future<Output = hyper::Result<Response<hyper::body::Incoming>>> {
-        let request = Request::get(uri)
-            .header(header::HOST, AUTHORITY)
-            .header(header::ACCEPT_ENCODING, GZIP)
-            .header(header::AUTHORIZATION, authorization)
-            .header(header::USER_AGENT, util::USER_AGENT)
-            .body(Empty::<Bytes>::new())
-            .unwrap();
-        request_sender.send_request(request)
-    }
-
-    inner(uri, authorization, request_sender)
+/// Deserializes an ID represented as a string.
+fn de_str_u64<'de, D: de::Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+    let s = String::deserialize(d)?;
+    s.parse().map_err(de::Error::custom)
+}
+
+/// Sends a `GET` request to a host other than Twitter, authorized with the `Authorization` header
+/// of `Token::AppOnly`, if any.
+fn send_get(
+    host: &str,
+    path_and_query: String,
+    token: &Token,
+    transport: &mut dyn Transport,
+) -> SendFuture {
+    let mut request = hyper::Request::get(path_and_query)
+        .header(header::HOST, HeaderValue::try_from(host).unwrap())
+        .header(header::ACCEPT_ENCODING, GZIP)
+        .header(header::USER_AGENT, util::USER_AGENT);
+    if let Token::AppOnly(ref authorization) = *token {
+        request = request.header(header::AUTHORIZATION, authorization);
+    }
+    transport.send(request.body(Empty::new()).unwrap())
+}
+
+/// Formats a list of IDs as a comma-separated parameter value.
+fn fmt_ids(ids: &[u64], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut ids = ids.iter();
+    if let Some(id) = ids.next() {
+        write!(f, "{}", id)?;
+        for id in ids {
+            write!(f, ",{}", id)?;
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,258 @@
+//! Client of the channel messages endpoint of the Discord API.
+
+use std::fmt::Write;
+
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+
+use crate::snowflake::Layout;
+use crate::transport::Transport;
+use crate::util;
+
+use super::response::Inner;
+use super::{
+    Kind, PagedRequest, RangeRequest, Request, ResponseFuture, TimelineItem, TimelineRequest, Token,
+};
+
+pub const HOST: &str = "discord.com";
+
+/// Type of the messages that reply to another message.
+const REPLY: u8 = 19;
+
+/// A request for the messages of a channel, polled with the `after` parameter.
+///
+/// The request is authorized with a bot token (`Token::from_bot`).
+#[derive(Clone, Debug, Serialize)]
+pub struct Messages {
+    channel_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<u64>,
+    /// Only sent without `after`, since the API accepts only one of them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<u64>,
+    limit: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Message {
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub id: u64,
+    /// The time the message was sent in ISO 8601 format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    pub author: Author,
+    #[serde(rename = "type")]
+    pub kind: u8,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Author {
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub id: u64,
+}
+
+impl Messages {
+    pub fn new(channel_id: u64) -> Self {
+        Messages {
+            channel_id,
+            after: None,
+            before: None,
+            limit: 100,
+        }
+    }
+
+    fn path_and_query(&self) -> String {
+        let mut ret = format!(
+            "/api/v10/channels/{}/messages?limit={}",
+            self.channel_id, self.limit
+        );
+        if let Some(after) = self.after {
+            write!(ret, "&after={}", after).unwrap();
+        } else if let Some(before) = self.before {
+            write!(ret, "&before={}", before).unwrap();
+        }
+        ret
+    }
+}
+
+impl Request for Messages {
+    fn host(&self) -> &str {
+        HOST
+    }
+
+    fn fetch<D>(&self, seed: D, token: &Token, transport: &mut dyn Transport) -> ResponseFuture<D>
+    where
+        D: for<'de> DeserializeSeed<'de>,
+    {
+        let response = super::send_get(HOST, self.path_and_query(), token, transport);
+        ResponseFuture {
+            inner: Inner::Response { response },
+            seed: Some(seed),
+            envelope: None,
+        }
+    }
+}
+
+impl TimelineRequest for Messages {
+    type Item = Message;
+
+    /// `after` returns the messages right after the given ID.
+    const OLDEST_FIRST: bool = true;
+
+    fn set_since_id(&mut self, since_id: Option<u64>) {
+        self.after = since_id;
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl PagedRequest for Messages {
+    type Envelope = Vec<Message>;
+}
+
+impl RangeRequest for Messages {
+    /// Sets `before` to the ID above the given one, since `before` is exclusive.
+    fn set_max_id(&mut self, max_id: Option<u64>) {
+        self.before = max_id.map(|id| id.saturating_add(1));
+    }
+}
+
+impl TimelineItem for Message {
+    const LAYOUT: Layout = Layout::DISCORD;
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn author_id(&self) -> Option<u64> {
+        Some(self.author.id)
+    }
+
+    fn kind(&self) -> Kind {
+        if self.kind == REPLY {
+            Kind::Reply
+        } else {
+            Kind::Original
+        }
+    }
+
+    /// Returns whether the timestamp of the ID agrees with `timestamp` to the millisecond.
+    fn is_created_at_consistent(&self) -> Option<bool> {
+        let timestamp_ms = util::parse_unix_ms(self.timestamp.as_deref()?)?;
+        Some(Self::LAYOUT.timestamp_ms(self.id) == timestamp_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use hyper::header;
+    use hyper::Response;
+
+    use crate::transport::Mock;
+
+    use super::*;
+
+    /// The example message of the API documentation followed by a reply to it.
+    const BODY: &str = r#"[
+  {
+    "reactions": [
+      {
+        "count": 1,
+        "count_details": { "burst": 0, "normal": 1 },
+        "me": false,
+        "me_burst": false,
+        "emoji": { "id": null, "name": "🔥" },
+        "burst_colors": []
+      }
+    ],
+    "attachments": [],
+    "tts": false,
+    "embeds": [],
+    "timestamp": "2017-07-11T17:27:07.299000+00:00",
+    "mention_everyone": false,
+    "id": "334385199974967042",
+    "pinned": false,
+    "edited_timestamp": null,
+    "author": {
+      "username": "Mason",
+      "discriminator": "9999",
+      "id": "53908099506183680",
+      "avatar": "a_bab14f271d565501444b2ca3be944b25"
+    },
+    "mention_roles": [],
+    "content": "Supa Hot",
+    "channel_id": "290926798999357250",
+    "mentions": [],
+    "type": 0
+  },
+  {
+    "attachments": [],
+    "tts": false,
+    "embeds": [],
+    "timestamp": "2017-07-11T17:28:11.652000+00:00",
+    "mention_everyone": false,
+    "id": "334385398790893575",
+    "pinned": false,
+    "edited_timestamp": null,
+    "author": { "username": "Alice", "id": "80351110224678912", "avatar": null },
+    "mention_roles": [],
+    "content": "Indeed",
+    "channel_id": "290926798999357250",
+    "mentions": [],
+    "message_reference": {
+      "type": 0,
+      "message_id": "334385199974967042",
+      "channel_id": "290926798999357250"
+    },
+    "type": 19
+  }
+]"#;
+
+    #[tokio::test]
+    async fn fetch_envelope() {
+        let path = "/api/v10/channels/290926798999357250/messages?limit=100";
+        let mut uris = vec![
+            format!("{}&after=334385199974967042", path),
+            format!("{}&before=334385398790893576", path),
+            path.to_owned(),
+        ];
+        let mut transport = Mock::new(move |request| {
+            assert_eq!(request.uri(), &uris.pop().unwrap()[..]);
+            assert_eq!(request.headers()[header::HOST], HOST);
+            assert_eq!(request.headers()[header::AUTHORIZATION], "Bot token");
+            Ok(Response::new(Bytes::from(BODY)))
+        });
+        let token = Token::from_bot("token").unwrap();
+        let mut request = Messages::new(290926798999357250);
+        let messages = request
+            .fetch_envelope(&token, &mut transport)
+            .await
+            .unwrap();
+
+        let [ref message, ref reply] = messages[..] else {
+            panic!("unexpected messages: {:?}", messages);
+        };
+        assert_eq!(message.id, 334385199974967042);
+        assert_eq!(message.author.id, 53908099506183680);
+        assert_eq!(message.kind(), Kind::Original);
+        assert_eq!(reply.id, 334385398790893575);
+        assert_eq!(reply.author.id, 80351110224678912);
+        assert_eq!(reply.kind(), Kind::Reply);
+        assert_eq!(reply.is_created_at_consistent(), Some(true));
+
+        request.set_max_id(Some(reply.id));
+        request
+            .fetch_envelope(&token, &mut transport)
+            .await
+            .unwrap();
+        // `after` takes the place of `before`.
+        request.set_since_id(Some(message.id));
+        request
+            .fetch_envelope(&token, &mut transport)
+            .await
+            .unwrap();
+    }
+}
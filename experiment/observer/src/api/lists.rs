@@ -1,10 +1,316 @@
-def_timelines! {
-    "/1.1/lists/statuses.json";
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Cursor, Envelope, PagedRequest, User};
+
+/// The maximum `count` accepted by the List timeline endpoint.
+pub const MAX_COUNT: usize = 200;
+
+/// A request for the statuses of a List, identified either by its ID or by its slug and owner.
+///
+/// The parameters left as `None` are omitted from the request so that the server defaults apply.
+#[derive(Clone, Debug, Serialize, oauth::Request, super::TimelineRequest)]
+#[timeline(version = "1.1", path = "/lists/statuses.json", item = super::Tweet)]
+pub struct Statuses {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    list_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner_screen_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[timeline(since_id)]
+    since_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[timeline(max_id)]
+    max_id: Option<u64>,
+    #[timeline(limit)]
+    count: usize,
+    include_entities: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include_rts: Option<bool>,
+    #[oauth1(fmt = fmt_tweet_mode)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tweet_mode: Option<TweetMode>,
+}
+
+/// The owner of a List identified by its slug.
+#[derive(Clone, Debug)]
+pub enum Owner {
+    ScreenName(String),
+    Id(u64),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TweetMode {
+    Compat,
+    Extended,
+}
+
+#[derive(Debug)]
+pub struct ParseTweetModeError(String);
+
+def_requests! {
+    "/1.1/lists/show.json";
     #[derive(Debug, oauth::Request)]
-    pub struct Statuses {
-        list_id: u64,
-        @since_id since_id: Option<u64>,
-        count: usize = 200,
+    pub struct Show {
+        list_id: u64;
+    }
+
+    "/1.1/lists/members.json";
+    #[derive(Clone, Debug, oauth::Request)]
+    pub struct Members {
+        list_id: u64;
+        count: usize = 5000,
+        cursor: Option<Cursor> = None,
         include_entities: bool = false,
+        skip_status: bool = true,
+    }
+
+    "/1.1/lists/members/create_all.json", POST;
+    #[derive(Debug, oauth::Request)]
+    pub struct MembersCreateAll {
+        list_id: u64,
+        #[oauth1(fmt = super::fmt_ids)]
+        user_id: Vec<u64>;
+    }
+
+    "/1.1/lists/members/destroy_all.json", POST;
+    #[derive(Debug, oauth::Request)]
+    pub struct MembersDestroyAll {
+        list_id: u64,
+        #[oauth1(fmt = super::fmt_ids)]
+        user_id: Vec<u64>;
+    }
+
+    "/1.1/lists/create.json", POST;
+    #[derive(Debug, oauth::Request)]
+    pub struct Create {
+        name: String,
+        #[oauth1(fmt = fmt_mode)]
+        mode: Mode,
+        description: Option<String>;
+    }
+
+    "/1.1/lists/ownerships.json";
+    #[derive(Clone, Debug, oauth::Request)]
+    pub struct Ownerships {
+        user_id: Option<u64>;
+        count: usize = 1000,
+        cursor: Option<Cursor> = None,
+    }
+}
+
+/// Maximum number of users that `MembersCreateAll` and `MembersDestroyAll` take at once.
+pub const MAX_MEMBERS_PER_REQUEST: usize = 100;
+
+/// A page of the members of a List.
+#[derive(Debug, Deserialize)]
+pub struct MembersPage {
+    pub users: Vec<User>,
+    /// The cursor of the next page, or `0` if this is the last page.
+    pub next_cursor_str: String,
+}
+
+/// A page of the Lists owned by a user.
+#[derive(Debug, Deserialize)]
+pub struct OwnershipsPage {
+    pub lists: Vec<List>,
+    /// The cursor of the next page, or `0` if this is the last page.
+    pub next_cursor_str: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct List {
+    pub id: u64,
+    pub name: String,
+    pub slug: String,
+    pub mode: Mode,
+    pub member_count: usize,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Public,
+    Private,
+}
+
+impl Statuses {
+    pub fn new(list_id: u64) -> Self {
+        Self::with_list(Some(list_id), None, None, None)
+    }
+
+    pub fn from_slug(slug: String, owner: Owner) -> Self {
+        let (owner_screen_name, owner_id) = match owner {
+            Owner::ScreenName(screen_name) => (Some(screen_name), None),
+            Owner::Id(id) => (None, Some(id)),
+        };
+        Self::with_list(None, Some(slug), owner_screen_name, owner_id)
+    }
+
+    fn with_list(
+        list_id: Option<u64>,
+        slug: Option<String>,
+        owner_screen_name: Option<String>,
+        owner_id: Option<u64>,
+    ) -> Self {
+        Statuses {
+            list_id,
+            slug,
+            owner_screen_name,
+            owner_id,
+            since_id: None,
+            max_id: None,
+            count: MAX_COUNT,
+            include_entities: false,
+            include_rts: None,
+            tweet_mode: None,
+        }
+    }
+
+    pub fn list_id(&self) -> Option<u64> {
+        self.list_id
+    }
+
+    pub fn set_count(&mut self, count: usize) {
+        self.count = count;
+    }
+
+    pub fn set_include_entities(&mut self, include_entities: bool) {
+        self.include_entities = include_entities;
+    }
+
+    /// Sets whether the timeline contains retweets, or `None` for the server default.
+    pub fn set_include_rts(&mut self, include_rts: Option<bool>) {
+        self.include_rts = include_rts;
+    }
+
+    pub fn set_tweet_mode(&mut self, tweet_mode: Option<TweetMode>) {
+        self.tweet_mode = tweet_mode;
+    }
+}
+
+impl PagedRequest for Members {
+    type Envelope = MembersPage;
+
+    fn set_cursor(&mut self, cursor: Option<Cursor>) {
+        self.cursor = cursor;
+    }
+}
+
+impl PagedRequest for Ownerships {
+    type Envelope = OwnershipsPage;
+
+    fn set_cursor(&mut self, cursor: Option<Cursor>) {
+        self.cursor = cursor;
+    }
+}
+
+impl Envelope for MembersPage {
+    type Item = User;
+
+    fn items(&self) -> &[User] {
+        &self.users
+    }
+
+    fn into_items(self) -> Vec<User> {
+        self.users
+    }
+
+    fn next_cursor(&self) -> Option<Cursor> {
+        next_cursor(&self.next_cursor_str)
+    }
+}
+
+impl Envelope for OwnershipsPage {
+    type Item = List;
+
+    fn items(&self) -> &[List] {
+        &self.lists
+    }
+
+    fn into_items(self) -> Vec<List> {
+        self.lists
+    }
+
+    fn next_cursor(&self) -> Option<Cursor> {
+        next_cursor(&self.next_cursor_str)
+    }
+}
+
+impl FromStr for TweetMode {
+    type Err = ParseTweetModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compat" => Ok(TweetMode::Compat),
+            "extended" => Ok(TweetMode::Extended),
+            _ => Err(ParseTweetModeError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for ParseTweetModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown tweet mode: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTweetModeError {}
+
+/// Returns the cursor of the next page from `next_cursor_str`, which is `0` at the end.
+fn next_cursor(next_cursor_str: &str) -> Option<Cursor> {
+    (next_cursor_str != "0").then(|| Cursor(next_cursor_str.to_owned()))
+}
+
+fn fmt_mode(mode: &Mode, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match *mode {
+        Mode::Public => "public",
+        Mode::Private => "private",
+    })
+}
+
+fn fmt_tweet_mode(tweet_mode: &TweetMode, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match *tweet_mode {
+        TweetMode::Compat => "compat",
+        TweetMode::Extended => "extended",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use hyper::Response;
+
+    use crate::api::Token;
+    use crate::transport::Mock;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fetch_members() {
+        let mut transport = Mock::new(|request| {
+            let query = request.uri().query().unwrap();
+            let body = if query.contains("cursor=1751419398386122211") {
+                r#"{"users":[{"id":3}],"next_cursor":0,"next_cursor_str":"0"}"#
+            } else {
+                assert!(!query.contains("cursor="), "{}", query);
+                r#"{"users":[{"id":1},{"id":2,"protected":true}],"next_cursor":1751419398386122211,"next_cursor_str":"1751419398386122211"}"#
+            };
+            Ok(Response::new(Bytes::from(body)))
+        });
+        let token = Token::from_bearer("token").unwrap();
+        let users = crate::api::fetch_pages(&Members::new(42), &token, &mut transport)
+            .await
+            .unwrap();
+        let ids: Vec<u64> = users.iter().map(|u| u.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(users[1].protected, Some(true));
     }
 }
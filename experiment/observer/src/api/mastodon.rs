@@ -0,0 +1,413 @@
+//! Client of the timeline endpoints of the Mastodon API.
+
+use std::fmt::{self, Write};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use futures_util::future;
+use hyper::header;
+use hyper::Uri;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+
+use crate::snowflake::Layout;
+use crate::transport::Transport;
+use crate::util;
+
+use super::response::Inner;
+use super::{
+    Kind, PagedRequest, RangeRequest, Request, ResponseFuture, TimelineItem, TimelineRequest, Token,
+};
+
+/// The characters to be percent-encoded in a hashtag in the path.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_');
+
+/// A request for a timeline of a Mastodon server.
+///
+/// The request is authorized with a bearer token (`Token::AppOnly`) and any other token is
+/// ignored, which is enough for the public timelines of most servers.
+#[derive(Clone, Debug, Serialize)]
+pub struct Statuses {
+    host: String,
+    timeline: Timeline,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    since_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_id: Option<u64>,
+    limit: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timeline {
+    Home,
+    List(u64),
+    /// The public timeline, or the local one if `local` is `true`.
+    Public {
+        local: bool,
+    },
+    Hashtag(String),
+}
+
+/// A page of a timeline, with the requests for the adjacent pages from the `Link` header.
+#[derive(Debug)]
+pub struct Page {
+    pub statuses: Vec<Status>,
+    /// The request for the page of the older statuses.
+    pub next: Option<Statuses>,
+    /// The request for the page of the newer statuses.
+    pub prev: Option<Statuses>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Status {
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub id: u64,
+    /// The creation time in ISO 8601 format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    pub account: Account,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reblog: Option<Reference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Account {
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub id: u64,
+}
+
+/// A reference to another status embedded in a status.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Reference {
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub id: u64,
+}
+
+#[derive(Debug)]
+pub struct ParseTimelineError(String);
+
+impl Statuses {
+    pub fn new(host: String, timeline: Timeline) -> Self {
+        Statuses {
+            host,
+            timeline,
+            since_id: None,
+            min_id: None,
+            max_id: None,
+            limit: 40,
+        }
+    }
+
+    /// Retrieves a page of the timeline along with the requests for the adjacent pages.
+    pub async fn fetch_page(
+        &self,
+        token: &Token,
+        transport: &mut dyn Transport,
+    ) -> anyhow::Result<Page> {
+        let response = super::send_get(&self.host, self.path_and_query(), token, transport).await?;
+        let links = response
+            .headers()
+            .get(header::LINK)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let mut next = None;
+        let mut prev = None;
+        for (rel, uri) in parse_links(links) {
+            let page = match rel {
+                "next" => &mut next,
+                "prev" => &mut prev,
+                _ => continue,
+            };
+            *page = self.with_query_of(uri);
+        }
+
+        let inner = Inner::Response {
+            response: Box::pin(future::ready(Ok(response))),
+        };
+        let statuses = ResponseFuture {
+            inner,
+            seed: Some(PhantomData::<Vec<Status>>),
+            envelope: None,
+        }
+        .await?;
+
+        Ok(Page {
+            statuses,
+            next,
+            prev,
+        })
+    }
+
+    fn path_and_query(&self) -> String {
+        let mut ret = match self.timeline {
+            Timeline::Home => "/api/v1/timelines/home".to_owned(),
+            Timeline::List(id) => format!("/api/v1/timelines/list/{}", id),
+            Timeline::Public { .. } => "/api/v1/timelines/public".to_owned(),
+            Timeline::Hashtag(ref tag) => format!(
+                "/api/v1/timelines/tag/{}",
+                utf8_percent_encode(tag, PATH_SEGMENT)
+            ),
+        };
+        write!(ret, "?limit={}", self.limit).unwrap();
+        if let Timeline::Public { local: true } = self.timeline {
+            ret.push_str("&local=true");
+        }
+        let params = [
+            ("since_id", self.since_id),
+            ("min_id", self.min_id),
+            ("max_id", self.max_id),
+        ];
+        for (name, value) in params {
+            if let Some(value) = value {
+                write!(ret, "&{}={}", name, value).unwrap();
+            }
+        }
+        ret
+    }
+
+    /// Returns the request with the pagination parameters of the given URI from a `Link` header.
+    fn with_query_of(&self, uri: &str) -> Option<Self> {
+        let uri = Uri::try_from(uri).ok()?;
+        let mut ret = Statuses {
+            since_id: None,
+            min_id: None,
+            max_id: None,
+            ..self.clone()
+        };
+        for param in uri.query()?.split('&') {
+            let (name, value) = param.split_once('=')?;
+            let id = match name {
+                "since_id" => &mut ret.since_id,
+                "min_id" => &mut ret.min_id,
+                "max_id" => &mut ret.max_id,
+                _ => continue,
+            };
+            *id = Some(value.parse().ok()?);
+        }
+        Some(ret)
+    }
+}
+
+impl Request for Statuses {
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    fn fetch<D>(&self, seed: D, token: &Token, transport: &mut dyn Transport) -> ResponseFuture<D>
+    where
+        D: for<'de> DeserializeSeed<'de>,
+    {
+        let response = super::send_get(&self.host, self.path_and_query(), token, transport);
+        ResponseFuture {
+            inner: Inner::Response { response },
+            seed: Some(seed),
+            envelope: None,
+        }
+    }
+}
+
+impl TimelineRequest for Statuses {
+    type Item = Status;
+
+    fn set_since_id(&mut self, since_id: Option<u64>) {
+        self.since_id = since_id;
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl PagedRequest for Statuses {
+    type Envelope = Vec<Status>;
+}
+
+impl RangeRequest for Statuses {
+    /// Sets `max_id` to the ID above the given one, since Mastodon's `max_id` is exclusive.
+    fn set_max_id(&mut self, max_id: Option<u64>) {
+        self.max_id = max_id.map(|id| id.saturating_add(1));
+    }
+}
+
+impl TimelineItem for Status {
+    const LAYOUT: Layout = Layout::MASTODON;
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn author_id(&self) -> Option<u64> {
+        Some(self.account.id)
+    }
+
+    fn kind(&self) -> Kind {
+        if self.reblog.is_some() {
+            Kind::Retweet
+        } else if self.in_reply_to_id.is_some() {
+            Kind::Reply
+        } else {
+            Kind::Original
+        }
+    }
+
+    /// Returns whether the timestamp of the ID falls in the second of `created_at`.
+    ///
+    /// The IDs of local statuses are derived from `created_at`, but the ones of remote statuses
+    /// may be minted at the time they were fetched.
+    fn is_created_at_consistent(&self) -> Option<bool> {
+        let created_at_ms = util::parse_unix_ms(self.created_at.as_deref()?)?;
+        Some(Self::LAYOUT.timestamp_ms(self.id) / 1000 == created_at_ms / 1000)
+    }
+}
+
+impl FromStr for Timeline {
+    type Err = ParseTimelineError;
+
+    /// Parses a timeline from `home`, `public`, `local`, `list:ID` or `tag:HASHTAG`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "home" => Ok(Timeline::Home),
+            None if s == "public" => Ok(Timeline::Public { local: false }),
+            None if s == "local" => Ok(Timeline::Public { local: true }),
+            Some(("list", id)) => id
+                .parse()
+                .map(Timeline::List)
+                .map_err(|_| ParseTimelineError(s.to_owned())),
+            Some(("tag", tag)) if !tag.is_empty() => Ok(Timeline::Hashtag(tag.to_owned())),
+            _ => Err(ParseTimelineError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for ParseTimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown timeline: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTimelineError {}
+
+/// Parses a `Link` header into the pairs of the `rel` parameter and the URI.
+fn parse_links(links: &str) -> impl Iterator<Item = (&str, &str)> {
+    links.split(',').filter_map(|link| {
+        let (uri, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+        let rel = params.split(';').find_map(|param| {
+            let value = param.trim().strip_prefix("rel=")?;
+            Some(value.trim_matches('"'))
+        })?;
+        Some((rel, uri))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use hyper::Response;
+
+    use crate::transport::Mock;
+
+    use super::*;
+
+    /// A response of `/api/v1/timelines/tag/rust` trimmed to two statuses.
+    const BODY: &str = r#"[
+  {
+    "id": "109317433532291627",
+    "created_at": "2022-11-10T03:40:59.000Z",
+    "in_reply_to_id": "109317420016302153",
+    "in_reply_to_account_id": "1",
+    "sensitive": false,
+    "spoiler_text": "",
+    "visibility": "public",
+    "language": "en",
+    "uri": "https://mastodon.example/users/alice/statuses/109317433532291627",
+    "url": "https://mastodon.example/@alice/109317433532291627",
+    "replies_count": 0,
+    "reblogs_count": 2,
+    "favourites_count": 5,
+    "edited_at": null,
+    "content": "<p>Indeed! <a href=\"https://mastodon.example/tags/rust\" class=\"mention hashtag\" rel=\"tag\">#<span>rust</span></a></p>",
+    "reblog": null,
+    "account": {
+      "id": "109302285712346321",
+      "username": "alice",
+      "acct": "alice",
+      "display_name": "Alice",
+      "locked": false,
+      "bot": false,
+      "created_at": "2022-11-07T00:00:00.000Z",
+      "followers_count": 12,
+      "emojis": [],
+      "fields": []
+    },
+    "media_attachments": [],
+    "mentions": [],
+    "tags": [{ "name": "rust", "url": "https://mastodon.example/tags/rust" }],
+    "emojis": [],
+    "card": null,
+    "poll": null
+  },
+  {
+    "id": "109317429198811904",
+    "created_at": "2022-11-10T03:39:53.000Z",
+    "in_reply_to_id": null,
+    "in_reply_to_account_id": null,
+    "visibility": "public",
+    "content": "",
+    "reblog": {
+      "id": "109317402751200030",
+      "created_at": "2022-11-10T03:33:10.000Z",
+      "in_reply_to_id": null,
+      "content": "<p>Hello, #rust</p>",
+      "reblog": null,
+      "account": { "id": "2", "username": "bob", "acct": "bob@remote.example" }
+    },
+    "account": { "id": "3", "username": "carol", "acct": "carol" },
+    "media_attachments": [],
+    "mentions": [],
+    "tags": [],
+    "emojis": []
+  }
+]"#;
+
+    #[tokio::test]
+    async fn fetch_page() {
+        let mut transport = Mock::new(|request| {
+            assert_eq!(request.uri(), "/api/v1/timelines/tag/rust?limit=40");
+            assert_eq!(request.headers()[header::HOST], "mastodon.example");
+            let response = Response::builder()
+                .header(
+                    header::LINK,
+                    "<https://mastodon.example/api/v1/timelines/tag/rust?max_id=109317429198811904>; rel=\"next\", \
+                     <https://mastodon.example/api/v1/timelines/tag/rust?min_id=109317433532291627>; rel=\"prev\"",
+                )
+                .body(Bytes::from(BODY))?;
+            Ok(response)
+        });
+        let request = Statuses::new("mastodon.example".to_owned(), "tag:rust".parse().unwrap());
+        let token = Token::from_bearer("token").unwrap();
+        let page = request.fetch_page(&token, &mut transport).await.unwrap();
+
+        let [ref reply, ref reblog] = page.statuses[..] else {
+            panic!("unexpected statuses: {:?}", page.statuses);
+        };
+        assert_eq!(reply.id, 109317433532291627);
+        assert_eq!(reply.account.id, 109302285712346321);
+        assert_eq!(reply.kind(), Kind::Reply);
+        assert_eq!(reply.is_created_at_consistent(), Some(true));
+        assert_eq!(reblog.id, 109317429198811904);
+        assert_eq!(reblog.reblog.as_ref().unwrap().id, 109317402751200030);
+        assert_eq!(reblog.kind(), Kind::Retweet);
+
+        let next = page.next.unwrap();
+        assert_eq!((next.since_id, next.min_id), (None, None));
+        assert_eq!(next.max_id, Some(109317429198811904));
+        let prev = page.prev.unwrap();
+        assert_eq!(prev.min_id, Some(109317433532291627));
+    }
+}
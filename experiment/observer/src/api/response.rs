@@ -1,3 +1,4 @@
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
@@ -7,13 +8,17 @@ use http_body_util::{combinators::Collect, BodyExt};
 use hyper::header;
 use hyper::StatusCode;
 use pin_project_lite::pin_project;
-use serde::de::DeserializeSeed;
+use serde::de::{self, DeserializeSeed, IgnoredAny};
+
+use crate::transport::{self, SendFuture};
 
 pin_project! {
     pub struct ResponseFuture<D> {
         #[pin]
         pub(super) inner: Inner,
         pub(super) seed: Option<D>,
+        // The field of the response object to deserialize the seed from, if any.
+        pub(super) envelope: Option<&'static str>,
     }
 }
 
@@ -21,11 +26,11 @@ pin_project! {
     #[project = InnerProj]
     pub(super) enum Inner {
         Response {
-            response: Pin<Box<dyn Future<Output = hyper::Result<hyper::Response<hyper::body::Incoming>>>>>,
+            response: SendFuture,
         },
         Body {
             #[pin]
-            body: Collect<hyper::body::Incoming>,
+            body: Collect<transport::Body>,
             gzip: bool,
         },
     }
@@ -36,6 +41,22 @@ struct Body<B> {
     gzip: bool,
 }
 
+/// A `DeserializeSeed` that deserializes `seed` from the `envelope` field of an object,
+/// or from the whole value if `envelope` is `None`.
+struct Enveloped<D> {
+    envelope: Option<&'static str>,
+    seed: D,
+}
+
+impl<D> ResponseFuture<D> {
+    /// Deserializes the whole response body, rather than the field of the envelope declared by
+    /// the endpoint (`Endpoint::ENVELOPE`).
+    pub fn ignore_envelope(mut self) -> Self {
+        self.envelope = None;
+        self
+    }
+}
+
 impl<D: for<'de> DeserializeSeed<'de>> Future for ResponseFuture<D> {
     type Output = anyhow::Result<<D as DeserializeSeed<'static>>::Value>;
 
@@ -45,16 +66,20 @@ impl<D: for<'de> DeserializeSeed<'de>> Future for ResponseFuture<D> {
         let Body { buf, gzip } = ready!(this.inner.poll(cx))?;
         let reader = buf.reader();
 
+        let seed = Enveloped {
+            envelope: *this.envelope,
+            seed: this.seed.take().unwrap(),
+        };
         let value = if gzip {
             let reader = flate2::bufread::GzDecoder::new(reader);
             let mut deserializer = serde_json::Deserializer::from_reader(reader);
-            this.seed.take().unwrap().deserialize(&mut deserializer)?
+            seed.deserialize(&mut deserializer)?
         } else {
             let mut deserializer = serde_json::Deserializer::from_reader(reader);
-            this.seed.take().unwrap().deserialize(&mut deserializer)?
+            seed.deserialize(&mut deserializer)?
         };
 
-        return Poll::Ready(Ok(value));
+        Poll::Ready(Ok(value))
     }
 }
 
@@ -68,17 +93,21 @@ impl Inner {
                 InnerProj::Response { response } => {
                     let response = ready!(response.as_mut().poll(cx))?;
 
+                    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                        let reset = response
+                            .headers()
+                            .get("x-rate-limit-reset")
+                            .and_then(|v| v.to_str().ok()?.parse().ok());
+                        return Poll::Ready(Err(super::RateLimitError { reset }.into()));
+                    }
                     if response.status() != StatusCode::OK {
-                        return Poll::Ready(Err(anyhow::anyhow!(
-                            "Bad status: {}",
-                            response.status()
-                        )));
+                        return Poll::Ready(Err(super::StatusError(response.status()).into()));
                     }
 
                     let gzip = response
                         .headers()
                         .get(header::CONTENT_ENCODING)
-                        .map_or(false, |v| v == super::GZIP);
+                        .is_some_and(|v| v == super::GZIP);
                     if !gzip {
                         tracing::debug!("Response is in `identity` encoding");
                     }
@@ -95,3 +124,48 @@ impl Inner {
         }
     }
 }
+
+impl<'de, D: DeserializeSeed<'de>> DeserializeSeed<'de> for Enveloped<D> {
+    type Value = D::Value;
+
+    fn deserialize<De: de::Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<D::Value, De::Error> {
+        struct Visitor<D> {
+            envelope: &'static str,
+            seed: D,
+        }
+
+        impl<'de, D: DeserializeSeed<'de>> de::Visitor<'de> for Visitor<D> {
+            type Value = D::Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "an object with `{}` field", self.envelope)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<D::Value, A::Error> {
+                let mut seed = Some(self.seed);
+                let mut value = None;
+                while let Some(key) = map.next_key::<std::borrow::Cow<'_, str>>()? {
+                    match seed.take() {
+                        Some(s) if key == self.envelope => value = Some(map.next_value_seed(s)?),
+                        s => {
+                            seed = s;
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                value.ok_or_else(|| de::Error::missing_field(self.envelope))
+            }
+        }
+
+        match self.envelope {
+            Some(envelope) => deserializer.deserialize_map(Visitor {
+                envelope,
+                seed: self.seed,
+            }),
+            None => self.seed.deserialize(deserializer),
+        }
+    }
+}
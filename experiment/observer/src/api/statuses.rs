@@ -0,0 +1,43 @@
+use serde::Serialize;
+
+def_requests! {
+    "/1.1/statuses/lookup.json";
+    #[derive(Debug, oauth::Request)]
+    pub struct Lookup {
+        #[oauth1(fmt = super::fmt_ids)]
+        id: Vec<u64>;
+        include_entities: bool = false,
+        map: bool = true,
+        trim_user: bool = true,
+    }
+}
+
+/// A request for the statuses posted by a user.
+#[derive(Clone, Debug, Serialize, oauth::Request, super::TimelineRequest)]
+#[timeline(version = "1.1", path = "/statuses/user_timeline.json", item = super::Tweet)]
+pub struct UserTimeline {
+    user_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[timeline(since_id)]
+    since_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[timeline(max_id)]
+    max_id: Option<u64>,
+    #[timeline(limit)]
+    count: usize,
+    include_rts: bool,
+    trim_user: bool,
+}
+
+impl UserTimeline {
+    pub fn new(user_id: u64) -> Self {
+        UserTimeline {
+            user_id,
+            since_id: None,
+            max_id: None,
+            count: 200,
+            include_rts: true,
+            trim_user: true,
+        }
+    }
+}
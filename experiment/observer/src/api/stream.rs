@@ -0,0 +1,283 @@
+//! Client of the streaming endpoints of the Twitter API v2.
+
+use std::time::{Duration, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use http_body_util::{BodyExt, Full};
+use hyper::client::conn::http2::SendRequest;
+use hyper::header::{self, HeaderValue};
+use hyper::Method;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::util;
+
+use super::{Token, AUTHORITY};
+
+const RULES_PATH: &str = "/2/tweets/search/stream/rules";
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(320);
+
+/// Tag of the rules managed by this program, which are replaced by `set_rules`.
+const RULE_TAG: &str = "leaky-snowflake-observer";
+/// Maximum length of a rule with the Elevated access.
+const MAX_RULE_LEN: usize = 512;
+
+/// A status delivered by the stream.
+#[derive(Debug, Deserialize)]
+pub struct StreamTweet {
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub id: u64,
+    #[serde(deserialize_with = "super::de_str_u64")]
+    pub author_id: u64,
+}
+
+/// A streaming endpoint.
+#[derive(Clone, Copy, Debug)]
+pub enum Endpoint {
+    /// The filtered stream, which delivers the statuses matching the rules set by `set_rules`.
+    Filtered,
+    /// The sampled stream, which delivers a random sample of all the public statuses.
+    Sample,
+}
+
+/// An open connection to a stream.
+pub struct Stream {
+    body: hyper::body::Incoming,
+    buf: BytesMut,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    data: Option<StreamTweet>,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Rules {
+    #[serde(default)]
+    data: Vec<Rule>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Rule {
+    #[serde(skip_serializing)]
+    id: Option<String>,
+    value: String,
+    tag: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RulesUpdate<'a> {
+    Add(Vec<Rule>),
+    Delete { ids: Vec<&'a str> },
+}
+
+/// Replaces the rules of the stream with the ones matching the statuses of the given users.
+pub async fn set_rules(
+    user_ids: &[u64],
+    token: &Token,
+    request_sender: &mut SendRequest<Full<Bytes>>,
+) -> anyhow::Result<()> {
+    let body = send(Method::GET, RULES_PATH, None, token, request_sender).await?;
+    let Rules { data } = serde_json::from_slice(&body.collect().await?.to_bytes())?;
+
+    let ids: Vec<&str> = data
+        .iter()
+        .filter(|rule| rule.tag.as_deref() == Some(RULE_TAG))
+        .filter_map(|rule| rule.id.as_deref())
+        .collect();
+    if !ids.is_empty() {
+        tracing::info!(?ids, "Deleting the stale stream rules");
+        update_rules(&RulesUpdate::Delete { ids }, token, request_sender).await?;
+    }
+
+    let mut rules = Vec::new();
+    let mut value = String::new();
+    for id in user_ids {
+        let clause = format!("from:{}", id);
+        if !value.is_empty() && value.len() + " OR ".len() + clause.len() > MAX_RULE_LEN {
+            rules.push(Rule {
+                id: None,
+                value: std::mem::take(&mut value),
+                tag: Some(RULE_TAG.to_owned()),
+            });
+        }
+        if !value.is_empty() {
+            value.push_str(" OR ");
+        }
+        value.push_str(&clause);
+    }
+    if !value.is_empty() {
+        rules.push(Rule {
+            id: None,
+            value,
+            tag: Some(RULE_TAG.to_owned()),
+        });
+    }
+    tracing::info!(rules = rules.len(), "Adding the stream rules");
+    update_rules(&RulesUpdate::Add(rules), token, request_sender).await
+}
+
+async fn update_rules(
+    update: &RulesUpdate<'_>,
+    token: &Token,
+    request_sender: &mut SendRequest<Full<Bytes>>,
+) -> anyhow::Result<()> {
+    let body = serde_json::to_vec(update)?;
+    let body = send(
+        Method::POST,
+        RULES_PATH,
+        Some(body.into()),
+        token,
+        request_sender,
+    )
+    .await?;
+    let Message { errors, .. } = serde_json::from_slice(&body.collect().await?.to_bytes())?;
+    if !errors.is_empty() {
+        anyhow::bail!("Error in updating the stream rules: {:?}", errors);
+    }
+    Ok(())
+}
+
+impl Stream {
+    pub async fn connect(
+        endpoint: Endpoint,
+        token: &Token,
+        request_sender: &mut SendRequest<Full<Bytes>>,
+    ) -> anyhow::Result<Self> {
+        let body = send(Method::GET, endpoint.path(), None, token, request_sender).await?;
+        Ok(Stream {
+            body,
+            buf: BytesMut::new(),
+        })
+    }
+
+    /// Returns the next status in the stream, or `None` if the stream has ended.
+    pub async fn next(&mut self) -> anyhow::Result<Option<StreamTweet>> {
+        loop {
+            while let Some(i) = self.buf.iter().position(|&b| b == b'\n') {
+                let line = self.buf.split_to(i + 1);
+                let line = line.trim_ascii();
+                // The stream sends blank lines as keep-alive signals.
+                if line.is_empty() {
+                    continue;
+                }
+                let message: Message = match serde_json::from_slice(line) {
+                    Ok(message) => message,
+                    Err(cause) => {
+                        tracing::error!(?cause, "Unable to parse stream message");
+                        continue;
+                    }
+                };
+                if let Some(tweet) = message.data {
+                    return Ok(Some(tweet));
+                }
+                tracing::error!(errors = ?message.errors, "Unknown stream message");
+            }
+
+            let Some(frame) = self.body.frame().await.transpose()? else {
+                return Ok(None);
+            };
+            if let Ok(data) = frame.into_data() {
+                self.buf.extend_from_slice(&data);
+            }
+        }
+    }
+}
+
+impl Endpoint {
+    fn path(self) -> &'static str {
+        match self {
+            Endpoint::Filtered => "/2/tweets/search/stream?tweet.fields=author_id",
+            Endpoint::Sample => "/2/tweets/sample/stream?tweet.fields=author_id",
+        }
+    }
+}
+
+/// Forwards the statuses from the stream to `sender` with the time they were delivered in Unix
+/// milliseconds, reconnecting with an exponential backoff, until the receiver is dropped.
+pub async fn forward(
+    endpoint: Endpoint,
+    token: Token,
+    request_sender: Option<SendRequest<Full<Bytes>>>,
+    sender: mpsc::UnboundedSender<(StreamTweet, u64)>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    let mut request_sender = request_sender;
+    loop {
+        // Use a fresh connection for every attempt after the first one, since the stream is
+        // usually cut off along with the connection.
+        let result = match request_sender.take() {
+            Some(s) => Ok(s),
+            None => util::http2_connect(super::HOST, util::HTTPS_DEFAULT_PORT).await,
+        };
+        let result = match result {
+            Ok(mut request_sender) => Stream::connect(endpoint, &token, &mut request_sender).await,
+            Err(cause) => Err(cause),
+        };
+
+        match result {
+            Ok(mut stream) => loop {
+                match stream.next().await {
+                    Ok(Some(tweet)) => {
+                        backoff = MIN_BACKOFF;
+                        let streamed_ms = util::time_to_unix_ms(SystemTime::now());
+                        if sender.send((tweet, streamed_ms)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {
+                        tracing::warn!("The stream has ended");
+                        break;
+                    }
+                    Err(cause) => {
+                        tracing::error!(?cause, "Error in the stream");
+                        break;
+                    }
+                }
+            },
+            Err(cause) => tracing::error!(?cause, "Error connecting to the stream"),
+        }
+
+        if sender.is_closed() {
+            return;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn send(
+    method: Method,
+    path: &str,
+    body: Option<Bytes>,
+    token: &Token,
+    request_sender: &mut SendRequest<Full<Bytes>>,
+) -> anyhow::Result<hyper::body::Incoming> {
+    let Token::AppOnly(ref authorization) = *token else {
+        anyhow::bail!("The filtered stream requires an app-only (bearer) token");
+    };
+    let mut request = hyper::Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::HOST, AUTHORITY)
+        .header(header::AUTHORIZATION, authorization)
+        .header(header::USER_AGENT, util::USER_AGENT);
+    if body.is_some() {
+        request = request.header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+    }
+    let request = request.body(Full::new(body.unwrap_or_default()))?;
+
+    let response = request_sender.send_request(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(super::StatusError(status).into());
+    }
+    Ok(response.into_body())
+}
@@ -0,0 +1,9 @@
+def_requests! {
+    "/1.1/users/lookup.json";
+    #[derive(Debug, oauth::Request)]
+    pub struct Lookup {
+        #[oauth1(fmt = super::fmt_ids)]
+        user_id: Vec<u64>;
+        include_entities: bool = false,
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// The result of a single poll of the timeline.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Poll {
+    pub nth: u64,
+    pub retrieved_ms: u64,
+    /// The `since_id` parameter of the request, if any.
+    pub since_id: Option<u64>,
+    /// Whether the response was filled up to the `count` parameter.
+    pub truncated: bool,
+    /// IDs of the statuses in the response, in reverse ID order.
+    pub ids: Vec<u64>,
+    /// The parameters of the request, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<serde_json::Value>,
+}
+
+pub struct Writer {
+    inner: BufWriter<File>,
+}
+
+impl Poll {
+    /// Returns the lowest ID which the response is guaranteed to contain if it is visible,
+    /// or `None` if the response covers the whole timeline.
+    pub fn lower_bound(&self) -> Option<u64> {
+        match self.ids.last() {
+            Some(&last) if self.truncated => Some(last),
+            _ => self.since_id.map(|since_id| since_id + 1),
+        }
+    }
+}
+
+impl Writer {
+    pub fn new(file: File) -> Self {
+        Writer {
+            inner: BufWriter::new(file),
+        }
+    }
+
+    pub fn write(&mut self, poll: &Poll) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.inner, poll)?;
+        writeln!(self.inner)?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the polls recorded as JSON Lines.
+pub fn read(reader: impl BufRead) -> anyhow::Result<Vec<Poll>> {
+    let mut ret = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        ret.push(serde_json::from_str(&line)?);
+    }
+    Ok(ret)
+}
+
+/// Opens the file at `path`, or the standard input if the `path` is `-`.
+pub fn open(path: &str) -> io::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        Ok(Box::new(io::BufReader::new(File::open(path)?)))
+    }
+}
@@ -0,0 +1,186 @@
+use std::ffi::OsString;
+use std::io::{self, stdout, BufRead, Write};
+use std::process::ExitCode;
+
+use getopts::{Matches, Options};
+use serde::Serialize;
+
+use leaky_snowflake_observer::snowflake::Layout;
+
+use crate::util;
+
+/// Parts of a decoded Snowflake ID.
+#[derive(Debug, Serialize)]
+struct Decoded {
+    id: u64,
+    timestamp: String,
+    unix_ms: u64,
+    datacenter_id: u64,
+    worker_id: u64,
+    sequence: u64,
+}
+
+pub fn main(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    layout_opts(&mut opts);
+    opts.optopt("", "format", "output format (default: csv)", "csv|json");
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} decode [OPTIONS..] [ID..]", program);
+        print!("{}", opts.usage(&brief));
+        println!("\nReads the IDs from the standard input if no ID is given.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let layout = layout(&matches)?;
+    let json = match matches.opt_str("format").as_deref() {
+        None | Some("csv") => false,
+        Some("json") => true,
+        Some(format) => anyhow::bail!("unknown format: {}", format),
+    };
+
+    let mut stdout = stdout().lock();
+    if !json {
+        writeln!(
+            stdout,
+            "id,timestamp,unix_ms,datacenter_id,worker_id,sequence"
+        )?;
+    }
+    for_each_input(&matches.free, |s| {
+        let id = s
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid ID: {}", s))?;
+        let unix_ms = layout
+            .checked_timestamp_ms(id)
+            .ok_or_else(|| anyhow::anyhow!("timestamp out of range: {}", id))?;
+        let decoded = Decoded {
+            id,
+            timestamp: util::format_unix_ms(unix_ms),
+            unix_ms,
+            datacenter_id: layout.datacenter_id(id),
+            worker_id: layout.worker_id(id),
+            sequence: layout.sequence(id),
+        };
+        if json {
+            serde_json::to_writer(&mut stdout, &decoded)?;
+            writeln!(stdout)?;
+        } else {
+            writeln!(
+                stdout,
+                "{},{},{},{},{},{}",
+                decoded.id,
+                decoded.timestamp,
+                decoded.unix_ms,
+                decoded.datacenter_id,
+                decoded.worker_id,
+                decoded.sequence,
+            )?;
+        }
+        Ok(())
+    })?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The `encode` subcommand, which is the inverse of `decode`.
+pub fn encode(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    layout_opts(&mut opts);
+    opts.optflag(
+        "",
+        "max",
+        "print the highest ID of the millisecond instead of the lowest one",
+    );
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        let brief = format!("Usage: {} encode [OPTIONS..] [TIME..]", program);
+        print!("{}", opts.usage(&brief));
+        println!(
+            "\nTIME is either Unix time in milliseconds or an ISO 8601 date-time in UTC like \
+            `2023-01-01T00:00:00.000Z`.\nReads the times from the standard input if no TIME is \
+            given."
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let layout = layout(&matches)?;
+    let max = matches.opt_present("max");
+
+    let mut stdout = stdout().lock();
+    for_each_input(&matches.free, |s| {
+        let unix_ms = s
+            .parse()
+            .ok()
+            .or_else(|| util::parse_unix_ms(s))
+            .ok_or_else(|| anyhow::anyhow!("invalid time: {}", s))?;
+        let id = if max {
+            layout.max_id_at(unix_ms)
+        } else {
+            layout.min_id_at(unix_ms)
+        };
+        let id = id.ok_or_else(|| anyhow::anyhow!("time out of range of the layout: {}", s))?;
+        writeln!(stdout, "{}", id)?;
+        Ok(())
+    })?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Adds the options for `layout`.
+pub fn layout_opts(opts: &mut Options) {
+    opts.optopt(
+        "",
+        "layout",
+        "layout of the IDs (default: twitter)",
+        "twitter|discord|mastodon",
+    );
+    opts.optopt(
+        "",
+        "epoch",
+        "override the epoch of the layout with Unix time MILLIS",
+        "MILLIS",
+    );
+}
+
+/// Returns the layout of the IDs specified by the options added by `layout_opts`.
+pub fn layout(matches: &Matches) -> anyhow::Result<Layout> {
+    let mut layout = match matches.opt_str("layout").as_deref() {
+        None | Some("twitter") => Layout::TWITTER,
+        Some("discord") => Layout::DISCORD,
+        Some("mastodon") => Layout::MASTODON,
+        Some(layout) => anyhow::bail!("unknown layout: {}", layout),
+    };
+    if let Some(epoch_ms) = matches.opt_get("epoch")? {
+        layout.epoch_ms = epoch_ms;
+    }
+    Ok(layout)
+}
+
+/// Calls `f` with each of the arguments, or with each of the whitespace-separated words of
+/// the standard input if there is no argument.
+fn for_each_input(
+    args: &[String],
+    mut f: impl FnMut(&str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if !args.is_empty() {
+        return args.iter().try_for_each(|arg| f(arg));
+    }
+    for line in io::stdin().lock().lines() {
+        line?.split_whitespace().try_for_each(&mut f)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Deduplication of the statuses fetched more than once by the overlapping polls.
+
+use std::collections::BTreeSet;
+
+/// A set of the IDs seen in the recent polls.
+///
+/// The adjusted `since_id` makes consecutive responses overlap, so the statuses in the overlap
+/// are fetched again. The IDs at or below the lower bound of `since_id`
+/// (`SinceIdPolicy::lower`) are never fetched again and can be expired, which keeps the set
+/// proportional to `k` times the rate of the timeline. The IDs at or below the last lower bound
+/// still count as seen.
+///
+/// The IDs are Snowflake IDs by default, but can be of any `TimePrefixed` scheme.
+#[derive(Clone, Debug)]
+pub struct Dedup<I = u64> {
+    seen: BTreeSet<I>,
+    expired: Option<I>,
+}
+
+impl<I: Ord> Dedup<I> {
+    pub fn new() -> Self {
+        Dedup {
+            seen: BTreeSet::new(),
+            expired: None,
+        }
+    }
+
+    /// Records the ID, returning `true` if it has not been seen before.
+    pub fn insert(&mut self, id: I) -> bool {
+        !self.is_expired(&id) && self.seen.insert(id)
+    }
+
+    pub fn contains(&self, id: &I) -> bool {
+        self.is_expired(id) || self.seen.contains(id)
+    }
+
+    /// Forgets the IDs at or below `lower`.
+    pub fn expire(&mut self, lower: &I)
+    where
+        I: Clone,
+    {
+        if self.is_expired(lower) {
+            return;
+        }
+        self.seen = self.seen.split_off(lower);
+        self.seen.remove(lower);
+        self.expired = Some(lower.clone());
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    fn is_expired(&self, id: &I) -> bool {
+        self.expired.as_ref().is_some_and(|expired| id <= expired)
+    }
+}
+
+impl<I: Ord> Default for Dedup<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expire() {
+        let mut dedup = Dedup::new();
+        for id in 1..=6 {
+            assert!(dedup.insert(id));
+        }
+        assert!(!dedup.insert(4));
+
+        dedup.expire(&3);
+        assert_eq!(dedup.len(), 3);
+        assert!((4..=6).all(|id| dedup.contains(&id)));
+        assert!(!dedup.insert(5));
+
+        // A duplicate from below the bound is not taken as a new ID.
+        assert!(!dedup.insert(2));
+        assert!(dedup.contains(&2));
+        assert_eq!(dedup.len(), 3);
+
+        // The bound does not move backwards.
+        dedup.expire(&1);
+        assert!(!dedup.insert(2));
+        assert!(dedup.insert(7));
+
+        dedup.expire(&6);
+        assert_eq!(dedup.len(), 1);
+        assert!(!dedup.insert(6));
+    }
+}
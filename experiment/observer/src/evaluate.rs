@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{stdout, Write};
+use std::process::ExitCode;
+
+use getopts::Options;
+use leaky_snowflake_observer::poller::SinceIdPolicy;
+use leaky_snowflake_observer::snowflake::Layout;
+use serde::Serialize;
+
+use crate::capture::{self, Poll};
+
+/// How a `since_id` policy would have performed on a captured run.
+#[derive(Debug, Default, Serialize)]
+struct Evaluation {
+    policy: &'static str,
+    k_ms: Option<u64>,
+    polls: u64,
+    /// Number of the polls in which a newly visible status would have been skipped.
+    leaked_polls: u64,
+    /// Number of the visible statuses that would have never been delivered.
+    lost_statuses: u64,
+    /// Number of the statuses that would have been delivered more than once.
+    duplicates: u64,
+    /// Number of the polls whose outcome cannot be determined because the `since_id` value
+    /// would have been lower than the range covered by the captured response.
+    indeterminate_polls: u64,
+}
+
+pub fn main(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "",
+        "min",
+        "lowest `k` value to evaluate (default: 0)",
+        "MILLIS",
+    );
+    opts.optopt(
+        "",
+        "max",
+        "highest `k` value to evaluate (default: 3000)",
+        "MILLIS",
+    );
+    opts.optopt(
+        "",
+        "step",
+        "interval of the `k` values (default: 100)",
+        "MILLIS",
+    );
+    opts.optopt("", "format", "output format (default: csv)", "csv|json");
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let polls = if let [ref path] = *matches.free {
+        capture::read(capture::open(path)?)?
+    } else {
+        println!("{}: missing CAPTURE argument", program);
+        print_usage(&program, &opts);
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let min = matches.opt_get_default("min", 0)?;
+    let max = matches.opt_get_default("max", 3000)?;
+    let step = matches.opt_get_default("step", 100)?;
+    if step == 0 {
+        anyhow::bail!("`--step` must be a positive integer");
+    }
+    let json = match matches.opt_str("format").as_deref() {
+        None | Some("csv") => false,
+        Some("json") => true,
+        Some(format) => anyhow::bail!("unknown format: {}", format),
+    };
+
+    let evaluations = std::iter::once(SinceIdPolicy::Naive)
+        .chain(
+            (min..=max)
+                .step_by(step)
+                .map(|k_ms| SinceIdPolicy::Adjusted { k_ms }),
+        )
+        .map(|policy| evaluate(&polls, policy));
+
+    let mut stdout = stdout().lock();
+    if json {
+        let evaluations: Vec<_> = evaluations.collect();
+        serde_json::to_writer_pretty(&mut stdout, &evaluations)?;
+        writeln!(stdout)?;
+    } else {
+        writeln!(
+            stdout,
+            "policy,k_ms,polls,leaked_polls,lost_statuses,duplicates,indeterminate_polls"
+        )?;
+        for e in evaluations {
+            writeln!(
+                stdout,
+                "{},{},{},{},{},{},{}",
+                e.policy,
+                e.k_ms.map_or_else(String::new, |k_ms| k_ms.to_string()),
+                e.polls,
+                e.leaked_polls,
+                e.lost_statuses,
+                e.duplicates,
+                e.indeterminate_polls,
+            )?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} evaluate [OPTIONS..] CAPTURE", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Replays the captured polls with the `since_id` values that `policy` would have used.
+fn evaluate(polls: &[Poll], policy: SinceIdPolicy) -> Evaluation {
+    let mut ret = Evaluation {
+        policy: match policy {
+            SinceIdPolicy::Naive => "naive",
+            SinceIdPolicy::Adjusted { .. } => "adjusted",
+        },
+        k_ms: match policy {
+            SinceIdPolicy::Naive => None,
+            SinceIdPolicy::Adjusted { k_ms } => Some(k_ms),
+        },
+        ..Evaluation::default()
+    };
+
+    // The poll in which each status appeared first.
+    let mut first_seen: HashMap<u64, usize> = HashMap::new();
+    for (i, poll) in polls.iter().enumerate() {
+        for &id in &poll.ids {
+            first_seen.entry(id).or_insert(i);
+        }
+    }
+
+    let mut latest: Option<(u64, u64)> = None;
+    let mut delivered = HashSet::with_capacity(first_seen.len());
+    for poll in polls {
+        ret.polls += 1;
+
+        let since_id = latest.map(|(latest_id, retrieved_ms)| {
+            policy.since_id(&Layout::TWITTER, latest_id, retrieved_ms)
+        });
+        if let (Some(since_id), Some(lower)) = (since_id, poll.lower_bound()) {
+            if since_id + 1 < lower {
+                ret.indeterminate_polls += 1;
+            }
+        }
+
+        let mut latest_id = latest.map(|(latest_id, _)| latest_id);
+        for &id in &poll.ids {
+            if since_id.is_some_and(|since_id| id <= since_id) {
+                continue;
+            }
+            if !delivered.insert(id) {
+                ret.duplicates += 1;
+            }
+            latest_id = latest_id.max(Some(id));
+        }
+        if let Some(latest_id) = latest_id {
+            latest = Some((latest_id, poll.retrieved_ms));
+        }
+    }
+
+    let mut leaked_polls = HashSet::new();
+    for (id, i) in first_seen {
+        if !delivered.contains(&id) {
+            ret.lost_statuses += 1;
+            leaked_polls.insert(i);
+        }
+    }
+    ret.leaked_polls = leaked_polls.len() as u64;
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T: u64 = 1539202764211;
+
+    fn id(ms: u64) -> u64 {
+        Layout::TWITTER.min_id_at(ms).unwrap()
+    }
+
+    /// Returns the lost, duplicate and indeterminate counts and the leaked polls.
+    fn counts(polls: &[Poll], policy: SinceIdPolicy) -> (u64, u64, u64, u64) {
+        let e = evaluate(polls, policy);
+        assert_eq!(e.polls, polls.len() as u64);
+        (
+            e.lost_statuses,
+            e.duplicates,
+            e.indeterminate_polls,
+            e.leaked_polls,
+        )
+    }
+
+    #[test]
+    fn late_status() {
+        let a = id(T - 500);
+        let b = id(T + 500);
+        // Appears only after `b`, 1700 ms past its timestamp.
+        let l = id(T + 300);
+        let c = id(T + 1500);
+        // The capture was made with a `since_id` 1000 ms behind the first poll.
+        let since_id = id(T - 1000) - 1;
+        let polls: Vec<Poll> = [vec![a], vec![b, a], vec![c, l, b, a], vec![c, l, b, a]]
+            .into_iter()
+            .zip(0..)
+            .map(|(ids, i)| Poll {
+                nth: i + 1,
+                retrieved_ms: T + 1000 * i,
+                since_id: Some(since_id),
+                truncated: false,
+                ids,
+                request: None,
+            })
+            .collect();
+
+        assert_eq!(counts(&polls, SinceIdPolicy::Naive), (1, 0, 0, 1));
+        assert_eq!(
+            counts(&polls, SinceIdPolicy::Adjusted { k_ms: 0 }),
+            (1, 0, 0, 1)
+        );
+        // `a`, `b` and `c` are returned again in the following polls.
+        assert_eq!(
+            counts(&polls, SinceIdPolicy::Adjusted { k_ms: 1000 }),
+            (0, 3, 0, 0)
+        );
+        // The second poll would have gone below the `since_id` of the capture.
+        assert_eq!(
+            counts(&polls, SinceIdPolicy::Adjusted { k_ms: 2000 }),
+            (0, 6, 1, 0)
+        );
+    }
+}
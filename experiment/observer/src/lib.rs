@@ -0,0 +1,15 @@
+//! Utilities for polling timelines ordered by k-sorted Snowflake IDs.
+
+// Lets the code generated by `leaky-snowflake-observer-derive` refer to this crate by its name.
+extern crate self as leaky_snowflake_observer;
+
+pub mod api;
+pub mod dedup;
+pub mod poller;
+pub mod snowflake;
+pub mod time_prefixed;
+pub mod transport;
+pub mod util;
+
+pub use self::dedup::Dedup;
+pub use self::poller::Poller;
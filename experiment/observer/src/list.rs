@@ -0,0 +1,135 @@
+use std::ffi::OsString;
+use std::io::{stdout, Write};
+use std::marker::PhantomData;
+use std::process::ExitCode;
+
+use getopts::Options;
+use serde::Serialize;
+
+use crate::api::lists::{self, List, Mode};
+use crate::api::{self, Request};
+use crate::transport;
+use crate::util;
+
+pub async fn main(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "",
+        "credentials",
+        "path to API credentials file (default: reads from `$HOME/.twurlrc` if any)",
+        "FILE",
+    );
+    opts.optflag("", "private", "make the List private (for `create`)");
+    opts.optopt(
+        "",
+        "description",
+        "description of the List (for `create`)",
+        "TEXT",
+    );
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let Some((command, args)) = matches.free.split_first() else {
+        println!("{}: missing COMMAND argument", program);
+        print_usage(&program, &opts);
+        return Ok(ExitCode::FAILURE);
+    };
+    let Some((token, _)) = crate::load_credentials(matches.opt_str("credentials"))? else {
+        println!("{}: missing `--credential` option and `.twurlrc`", program);
+        print_usage(&program, &opts);
+        return Ok(ExitCode::FAILURE);
+    };
+    let token = api::Token::from(token);
+    let mut transport = transport::Http2::connect(api::HOST, util::HTTPS_DEFAULT_PORT).await?;
+    let mut stdout = stdout().lock();
+
+    if let ("create", [name]) = (&**command, args) {
+        let mode = if matches.opt_present("private") {
+            Mode::Private
+        } else {
+            Mode::Public
+        };
+        let list = lists::Create::new(name.clone(), mode, matches.opt_str("description"))
+            .fetch(PhantomData::<List>, &token, &mut transport)
+            .await?;
+        write_json(&mut stdout, &list)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let ids = args
+        .iter()
+        .map(|arg| arg.parse())
+        .collect::<Result<Vec<u64>, _>>()?;
+    match (&**command, &*ids) {
+        ("show", &[list_id]) => {
+            let list = lists::Show::new(list_id)
+                .fetch(PhantomData::<List>, &token, &mut transport)
+                .await?;
+            write_json(&mut stdout, &list)?;
+        }
+        ("members", &[list_id]) => {
+            let request = lists::Members::new(list_id);
+            let users = api::fetch_pages(&request, &token, &mut transport).await?;
+            for user in users {
+                write_json(&mut stdout, &user)?;
+            }
+        }
+        ("add" | "remove", &[list_id, ref user_ids @ ..]) if !user_ids.is_empty() => {
+            for chunk in user_ids.chunks(lists::MAX_MEMBERS_PER_REQUEST) {
+                let list = if command == "add" {
+                    lists::MembersCreateAll::new(list_id, chunk.to_vec())
+                        .fetch(PhantomData::<List>, &token, &mut transport)
+                        .await?
+                } else {
+                    lists::MembersDestroyAll::new(list_id, chunk.to_vec())
+                        .fetch(PhantomData::<List>, &token, &mut transport)
+                        .await?
+                };
+                write_json(&mut stdout, &list)?;
+            }
+        }
+        ("ownerships", &[]) | ("ownerships", &[_]) => {
+            let request = lists::Ownerships::new(ids.first().copied());
+            let lists = api::fetch_pages(&request, &token, &mut transport).await?;
+            for list in lists {
+                write_json(&mut stdout, &list)?;
+            }
+        }
+        _ => {
+            println!("{}: invalid arguments for `{}`", program, command);
+            print_usage(&program, &opts);
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {0} list [OPTIONS..] show LIST_ID\n       \
+        {0} list [OPTIONS..] members LIST_ID\n       \
+        {0} list [OPTIONS..] add LIST_ID USER_ID..\n       \
+        {0} list [OPTIONS..] remove LIST_ID USER_ID..\n       \
+        {0} list [OPTIONS..] create NAME\n       \
+        {0} list [OPTIONS..] ownerships [USER_ID]",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+}
+
+fn write_json(mut w: impl Write, value: &impl Serialize) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut w, value)?;
+    writeln!(w)?;
+    Ok(())
+}
@@ -1,9 +1,15 @@
-mod api;
+mod analyze;
+mod capture;
+mod decode;
+mod evaluate;
+mod list;
+mod populate;
 mod run;
+mod simulate;
 mod twurlrc;
-mod util;
 
 use std::env;
+use std::ffi::OsString;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
@@ -11,20 +17,62 @@ use std::ops::ControlFlow;
 use std::process::ExitCode;
 
 use getopts::Options;
+use leaky_snowflake_observer::api::RangeRequest;
+use leaky_snowflake_observer::{api, transport, util};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<ExitCode> {
     tracing_subscriber::fmt::init();
-    let args = match process_args(env::args_os())? {
+
+    let mut args: Vec<OsString> = env::args_os().collect();
+    let subcommand: Option<fn(Vec<OsString>) -> anyhow::Result<ExitCode>> =
+        match args.get(1).and_then(|arg| arg.to_str()) {
+            Some("analyze") => Some(analyze::main),
+            Some("decode") => Some(decode::main),
+            Some("encode") => Some(decode::encode),
+            Some("evaluate") => Some(evaluate::main),
+            Some("simulate") => Some(simulate::main),
+            _ => None,
+        };
+    if let Some(subcommand) = subcommand {
+        args.remove(1);
+        return subcommand(args);
+    }
+    // Subcommands that access the API.
+    match args.get(1).and_then(|arg| arg.to_str()) {
+        Some("list") => {
+            args.remove(1);
+            return list::main(args).await;
+        }
+        Some("populate") => {
+            args.remove(1);
+            return populate::main(args).await;
+        }
+        _ => {}
+    }
+
+    let (request, args) = match process_args(args.into_iter())? {
         ControlFlow::Continue(args) => args,
         ControlFlow::Break(code) => return Ok(code),
     };
-    run::run(args).await.and(Ok(ExitCode::SUCCESS))
+    match request {
+        Timeline::Twitter(request) => run::run(args.with_request(request)).await?,
+        Timeline::Mastodon(request) => run::run(args.with_request(request)).await?,
+        Timeline::Discord(request) => run::run(args.with_request(request)).await?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The timeline to be observed.
+enum Timeline {
+    Twitter(api::lists::Statuses),
+    Mastodon(api::mastodon::Statuses),
+    Discord(api::discord::Messages),
 }
 
 fn process_args(
-    mut args: env::ArgsOs,
-) -> anyhow::Result<ControlFlow<ExitCode, run::Args<api::lists::Statuses>>> {
+    mut args: impl Iterator<Item = OsString>,
+) -> anyhow::Result<ControlFlow<ExitCode, (Timeline, run::Args<()>)>> {
     let program = args.next().unwrap();
 
     let mut opts = Options::new();
@@ -34,12 +82,145 @@ fn process_args(
         "path to API credentials file (default: reads from `$HOME/.twurlrc` if any)",
         "FILE",
     );
+    opts.optopt(
+        "",
+        "mastodon",
+        "observe the TIMELINE (`home`, `public`, `local`, `list:ID` or `tag:HASHTAG`) of the \
+        Mastodon server at HOST instead of a Twitter List (the credentials file should have \
+        an `access_token`)",
+        "HOST",
+    );
+    opts.optflag(
+        "",
+        "discord",
+        "observe the Discord channel of the given ID instead of a Twitter List (the credentials \
+        file should have a `bot_token`)",
+    );
+    opts.optopt(
+        "",
+        "slug",
+        "observe the Twitter List of the given SLUG instead of LIST_ID, owned by the user of \
+        `--owner` or `--owner-id`",
+        "SLUG",
+    );
+    opts.optopt(
+        "",
+        "owner",
+        "screen name of the owner of the `--slug` List",
+        "SCREEN_NAME",
+    );
+    opts.optopt(
+        "",
+        "owner-id",
+        "user ID of the owner of the `--slug` List",
+        "USER_ID",
+    );
+    opts.optopt(
+        "",
+        "count",
+        "`count` parameter of the List timeline requests, up to 200 (default: 200)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "max-id",
+        "`max_id` parameter of the List timeline requests",
+        "ID",
+    );
+    opts.optopt(
+        "",
+        "include-rts",
+        "`include_rts` parameter of the List timeline requests (`true` or `false`, default: \
+        omitted)",
+        "BOOL",
+    );
+    opts.optflag(
+        "",
+        "include-entities",
+        "set `include_entities` parameter of the List timeline requests",
+    );
+    opts.optopt(
+        "",
+        "tweet-mode",
+        "`tweet_mode` parameter of the List timeline requests (`compat` or `extended`)",
+        "MODE",
+    );
     opts.optopt(
         "k",
         "",
         "assume the `k` value to be MILLIS ms (default: 1000)",
         "MILLIS",
     );
+    opts.optopt(
+        "",
+        "history",
+        "number of past timelines to compare new statuses against (default: 8)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "latency",
+        "write the visibility latency of every status to FILE as JSON Lines",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "latency-window",
+        "number of recent statuses to compute the latency percentiles over (default: 1000)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "vanished",
+        "write the statuses that disappeared from the timeline to FILE as JSON Lines",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "workers",
+        "write the ordering violations and leaks by Snowflake worker to FILE as JSON Lines",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "stream",
+        "compare the timeline against the filtered stream of the List members and write \
+        the statuses missed by the polling to FILE as JSON Lines (requires a bearer token)",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "stream-budget",
+        "time allowed for a streamed status to appear in the timeline (default: 60000)",
+        "MILLIS",
+    );
+    opts.optflag(
+        "",
+        "compare",
+        "poll alternately with the naive and the adjusted `since_id` and report the differences",
+    );
+    opts.optflag(
+        "",
+        "probe",
+        "on a leak, bisect the `since_id` values for which the leaked status is returned",
+    );
+    opts.optflag(
+        "",
+        "full",
+        "retrieve the latest statuses without `since_id` every time, e.g. for `--capture`",
+    );
+    opts.optflag("", "keep-going", "keep polling after reporting a leak");
+    opts.optopt(
+        "",
+        "capture",
+        "write the IDs retrieved in every poll to FILE as JSON Lines, for `evaluate` subcommand",
+        "FILE",
+    );
+    opts.optflag(
+        "",
+        "http1",
+        "connect to the API over HTTP/1.1 instead of HTTP/2",
+    );
     opts.optflag("h", "help", "print this help");
 
     let matches = opts.parse(args)?;
@@ -50,38 +231,174 @@ fn process_args(
         return Ok(ControlFlow::Break(ExitCode::SUCCESS));
     }
 
-    let list_id = if let [ref s] = *matches.free {
-        s.parse()?
-    } else {
-        let program = program.to_string_lossy();
-        println!("{}: missing LIST_ID argument", program);
-        print_usage(&program, &opts);
-        return Ok(ControlFlow::Break(ExitCode::FAILURE));
+    // The List is given by `--slug` instead of the LIST_ID argument, if any.
+    let timeline = match *matches.free {
+        [ref timeline] if !matches.opt_present("slug") => Some(timeline),
+        [] if matches.opt_present("slug") => None,
+        _ => {
+            let program = program.to_string_lossy();
+            println!("{}: missing LIST_ID argument", program);
+            print_usage(&program, &opts);
+            return Ok(ControlFlow::Break(ExitCode::FAILURE));
+        }
     };
-    let request = api::lists::Statuses::new(list_id);
+    let twitter_only = [
+        "stream",
+        "capture",
+        "slug",
+        "owner",
+        "owner-id",
+        "count",
+        "max-id",
+        "include-rts",
+        "include-entities",
+        "tweet-mode",
+    ];
+    if matches.opt_present("mastodon") || matches.opt_present("discord") {
+        if let Some(name) = twitter_only.iter().find(|&&name| matches.opt_present(name)) {
+            anyhow::bail!("`--{}` is only supported for Twitter", name);
+        }
+    }
 
     let k_ms = matches.opt_get_default("k", 1000)?;
+    let history_len = matches.opt_get_default("history", 8)?;
+    if history_len == 0 {
+        anyhow::bail!("`--history` must be a positive integer");
+    }
+    let latency_window = matches.opt_get_default("latency-window", 1000)?;
+    if latency_window == 0 {
+        anyhow::bail!("`--latency-window` must be a positive integer");
+    }
+    let latency_output = matches.opt_str("latency").map(File::create).transpose()?;
+    let vanished_output = matches.opt_str("vanished").map(File::create).transpose()?;
+    let workers_output = matches.opt_str("workers").map(File::create).transpose()?;
+
+    let (request, token, stream) = if let Some(host) = matches.opt_str("mastodon") {
+        let request = api::mastodon::Statuses::new(host, timeline.unwrap().parse()?);
+        let Some(credentials) = matches.opt_str("credentials") else {
+            let program = program.to_string_lossy();
+            println!("{}: `--mastodon` requires `--credentials` option", program);
+            print_usage(&program, &opts);
+            return Ok(ControlFlow::Break(ExitCode::FAILURE));
+        };
+        let Some(token) = api::Token::from_bearer(&load_token(&credentials, "access_token")?)
+        else {
+            anyhow::bail!("invalid access token");
+        };
+        (Timeline::Mastodon(request), token, None)
+    } else if matches.opt_present("discord") {
+        let request = api::discord::Messages::new(timeline.unwrap().parse()?);
+        let Some(credentials) = matches.opt_str("credentials") else {
+            let program = program.to_string_lossy();
+            println!("{}: `--discord` requires `--credentials` option", program);
+            print_usage(&program, &opts);
+            return Ok(ControlFlow::Break(ExitCode::FAILURE));
+        };
+        let Some(token) = api::Token::from_bot(&load_token(&credentials, "bot_token")?) else {
+            anyhow::bail!("invalid bot token");
+        };
+        (Timeline::Discord(request), token, None)
+    } else {
+        let mut request = if let Some(slug) = matches.opt_str("slug") {
+            let owner = match (matches.opt_str("owner"), matches.opt_get("owner-id")?) {
+                (Some(screen_name), None) => api::lists::Owner::ScreenName(screen_name),
+                (None, Some(id)) => api::lists::Owner::Id(id),
+                _ => anyhow::bail!("`--slug` requires either `--owner` or `--owner-id`"),
+            };
+            api::lists::Statuses::from_slug(slug, owner)
+        } else {
+            api::lists::Statuses::new(timeline.unwrap().parse()?)
+        };
+        let count = matches.opt_get_default("count", api::lists::MAX_COUNT)?;
+        if !(1..=api::lists::MAX_COUNT).contains(&count) {
+            anyhow::bail!("`--count` must be between 1 and {}", api::lists::MAX_COUNT);
+        }
+        request.set_count(count);
+        request.set_max_id(matches.opt_get("max-id")?);
+        request.set_include_rts(matches.opt_get("include-rts")?);
+        request.set_include_entities(matches.opt_present("include-entities"));
+        request.set_tweet_mode(matches.opt_get("tweet-mode")?);
+
+        let Some((token, bearer)) = load_credentials(matches.opt_str("credentials"))? else {
+            let program = program.to_string_lossy();
+            println!("{}: missing `--credential` option and `.twurlrc`", program);
+            print_usage(&program, &opts);
+            return Ok(ControlFlow::Break(ExitCode::FAILURE));
+        };
+        let token = api::Token::from(token);
+
+        let stream = if let Some(output) = matches.opt_str("stream") {
+            let Some(list_id) = request.list_id() else {
+                anyhow::bail!("`--stream` requires a LIST_ID rather than `--slug`");
+            };
+            let Some(bearer) = bearer else {
+                anyhow::bail!("`--stream` requires a bearer token in the credentials");
+            };
+            let Some(token) = api::Token::from_bearer(&bearer) else {
+                anyhow::bail!("invalid bearer token");
+            };
+            Some(run::StreamArgs {
+                members: api::lists::Members::new(list_id),
+                token,
+                budget_ms: matches.opt_get_default("stream-budget", 60000)?,
+                output: File::create(output)?,
+            })
+        } else {
+            None
+        };
+
+        (Timeline::Twitter(request), token, stream)
+    };
+
+    let args = run::Args {
+        request: (),
+        k_ms,
+        history_len,
+        latency_window,
+        latency_output,
+        vanished_output,
+        workers_output,
+        stream,
+        compare: matches.opt_present("compare"),
+        probe: matches.opt_present("probe"),
+        full: matches.opt_present("full"),
+        keep_going: matches.opt_present("keep-going"),
+        capture_output: matches.opt_str("capture").map(File::create).transpose()?,
+        http1: matches.opt_present("http1"),
+        token,
+    };
+    Ok(ControlFlow::Continue((request, args)))
+}
 
-    let token = if let Some(credentials) = matches.opt_str("credentials") {
+/// Reads the API credentials from the given file or `$HOME/.twurlrc`, returning the user token
+/// and the bearer token if any, or `None` if neither is available.
+fn load_credentials(
+    credentials: Option<String>,
+) -> anyhow::Result<Option<(oauth::Token, Option<String>)>> {
+    if let Some(credentials) = credentials {
         #[derive(serde::Deserialize)]
         struct Credentials {
             consumer_key: String,
             consumer_secret: String,
             access_token: String,
             access_token_secret: String,
+            #[serde(default)]
+            bearer_token: Option<String>,
         }
         let Credentials {
             consumer_key,
             consumer_secret,
             access_token,
             access_token_secret,
+            bearer_token,
         } = serde_json::from_reader(BufReader::new(File::open(credentials)?))?;
-        oauth::Token::from_parts(
+        let token = oauth::Token::from_parts(
             consumer_key,
             consumer_secret,
             access_token,
             access_token_secret,
-        )
+        );
+        Ok(Some((token, bearer_token)))
     } else if let Some(f) = dirs::home_dir()
         .and_then(|mut home| {
             home.push(".twurlrc");
@@ -98,23 +415,36 @@ fn process_args(
             profile.username,
             "Using default credentials from `.twurlrc`"
         );
-        profile.token
+        Ok(Some((profile.token, profile.bearer)))
     } else {
-        let program = program.to_string_lossy();
-        println!("{}: missing `--credential` option and `.twurlrc`", program);
-        print_usage(&program, &opts);
-        return Ok(ControlFlow::Break(ExitCode::FAILURE));
-    };
-    let token = api::Token::from(token);
+        Ok(None)
+    }
+}
 
-    Ok(ControlFlow::Continue(run::Args {
-        request,
-        k_ms,
-        token,
-    }))
+/// Reads the token in the given field of a credentials file of a service other than Twitter.
+fn load_token(credentials: &str, field: &str) -> anyhow::Result<String> {
+    let credentials: serde_json::Value =
+        serde_json::from_reader(BufReader::new(File::open(credentials)?))?;
+    match credentials.get(field).and_then(|v| v.as_str()) {
+        Some(token) => Ok(token.to_owned()),
+        None => anyhow::bail!("missing `{}` in the credentials file", field),
+    }
 }
 
 fn print_usage(program: &str, opts: &Options) {
-    let brief = format!("Usage: {} [OPTIONS..] LIST_ID", program);
+    let brief = format!(
+        "Usage: {0} [OPTIONS..] LIST_ID\n       \
+        {0} [OPTIONS..] --slug SLUG (--owner SCREEN_NAME | --owner-id USER_ID)\n       \
+        {0} [OPTIONS..] --mastodon HOST TIMELINE\n       \
+        {0} [OPTIONS..] --discord CHANNEL_ID\n       \
+        {0} evaluate [OPTIONS..] CAPTURE\n       \
+        {0} simulate [OPTIONS..]\n       \
+        {0} decode [OPTIONS..] [ID..]\n       \
+        {0} encode [OPTIONS..] [TIME..]\n       \
+        {0} analyze [OPTIONS..] [FILE..]\n       \
+        {0} populate [OPTIONS..] LIST_ID\n       \
+        {0} list [OPTIONS..] COMMAND [ARGS..]",
+        program
+    );
     print!("{}", opts.usage(&brief));
 }
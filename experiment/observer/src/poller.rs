@@ -0,0 +1,387 @@
+//! A polling client that does not miss the statuses leaking into a timeline late.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use futures_util::stream::{self, BoxStream, Stream};
+
+use crate::api::{self, Envelope, RangeRequest, TimelineItem};
+use crate::dedup::Dedup;
+use crate::time_prefixed::TimePrefixed;
+use crate::transport::{self, Transport};
+use crate::util;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Strategy to determine the `since_id` parameter of the next request.
+#[derive(Clone, Copy, Debug)]
+pub enum SinceIdPolicy {
+    /// Uses the ID of the latest status as is, which is prone to timeline leaks.
+    Naive,
+    /// Adjusts the ID so that its timestamp is at least `k_ms` earlier than both the latest
+    /// status and the time of the last request, as proposed in the article.
+    Adjusted { k_ms: u64 },
+}
+
+/// A `Stream` of the statuses of a timeline, polled with the adjusted `since_id`.
+///
+/// Each status is yielded exactly once and in ascending ID order. Since a status with a lower
+/// ID may still appear in the timeline until `k_ms` has passed since its timestamp, a status is
+/// held back until the following requests can no longer return anything below it.
+///
+/// When a response is full, the rest of the statuses since the previous request are retrieved
+/// from the following pages before `since_id` moves past them.
+///
+/// The first poll yields the statuses already in the timeline as well. Errors in the requests
+/// are yielded as they occur and the polling is resumed on the next poll of the stream.
+pub struct Poller<T> {
+    inner: BoxStream<'static, anyhow::Result<T>>,
+}
+
+struct State<R: RangeRequest> {
+    request: R,
+    token: api::Token,
+    policy: SinceIdPolicy,
+    interval: tokio::time::Interval,
+    /// The transport, or `None` to connect to the host of the request over HTTP/2 on the first
+    /// poll.
+    transport: Option<Box<dyn Transport>>,
+    /// Whether the connection of the transport has to be re-established before the next request.
+    broken: bool,
+    /// The highest ID observed so far and the time of the request that observed it.
+    latest: Option<(u64, u64)>,
+    dedup: Dedup,
+    /// Statuses that may still be preceded by the ones yet to appear, keyed by their IDs.
+    pending: BTreeMap<u64, R::Item>,
+    ready: VecDeque<R::Item>,
+    timeline: Vec<R::Item>,
+}
+
+impl SinceIdPolicy {
+    /// Returns the `since_id` value for the request following the one initiated at
+    /// `retrieved_ms`, where `latest_id` is the highest ID observed so far.
+    pub fn since_id<S: TimePrefixed>(
+        self,
+        scheme: &S,
+        latest_id: S::Id,
+        retrieved_ms: u64,
+    ) -> S::Id {
+        match self {
+            SinceIdPolicy::Naive => latest_id,
+            SinceIdPolicy::Adjusted { k_ms } => scheme
+                .id_before(retrieved_ms.saturating_sub(k_ms))
+                .clamp(self.lower(scheme, latest_id), latest_id),
+        }
+    }
+
+    /// Returns the lowest value that `since_id` can take given `latest_id`, so that the IDs at or
+    /// below it are never returned by the following requests.
+    pub fn lower<S: TimePrefixed>(self, scheme: &S, latest_id: S::Id) -> S::Id {
+        match self {
+            SinceIdPolicy::Naive => latest_id,
+            SinceIdPolicy::Adjusted { k_ms } => {
+                scheme.id_before(scheme.timestamp_ms(latest_id).saturating_sub(k_ms))
+            }
+        }
+    }
+}
+
+impl<T: TimelineItem + Send + 'static> Poller<T> {
+    pub fn new<R>(request: R, token: api::Token, k_ms: u64) -> Self
+    where
+        R: RangeRequest<Item = T> + Send + Sync + 'static,
+        R::Envelope: Envelope<Item = T> + Send,
+    {
+        Self::with_interval(request, token, k_ms, DEFAULT_INTERVAL)
+    }
+
+    /// Creates a `Poller` that polls the timeline every `interval` instead of every second.
+    pub fn with_interval<R>(request: R, token: api::Token, k_ms: u64, interval: Duration) -> Self
+    where
+        R: RangeRequest<Item = T> + Send + Sync + 'static,
+        R::Envelope: Envelope<Item = T> + Send,
+    {
+        Self::from_parts(request, None, token, k_ms, interval)
+    }
+
+    /// Creates a `Poller` that sends the requests with the given transport, e.g. a `Mock`.
+    pub fn with_transport<R>(
+        request: R,
+        transport: impl Transport + 'static,
+        token: api::Token,
+        k_ms: u64,
+        interval: Duration,
+    ) -> Self
+    where
+        R: RangeRequest<Item = T> + Send + Sync + 'static,
+        R::Envelope: Envelope<Item = T> + Send,
+    {
+        Self::from_parts(request, Some(Box::new(transport)), token, k_ms, interval)
+    }
+
+    fn from_parts<R>(
+        request: R,
+        transport: Option<Box<dyn Transport>>,
+        token: api::Token,
+        k_ms: u64,
+        interval: Duration,
+    ) -> Self
+    where
+        R: RangeRequest<Item = T> + Send + Sync + 'static,
+        R::Envelope: Envelope<Item = T> + Send,
+    {
+        let state = State {
+            request,
+            token,
+            policy: SinceIdPolicy::Adjusted { k_ms },
+            interval: tokio::time::interval(interval),
+            transport,
+            broken: false,
+            latest: None,
+            dedup: Dedup::new(),
+            pending: BTreeMap::new(),
+            ready: VecDeque::new(),
+            timeline: Vec::new(),
+        };
+        let inner = stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(t) = state.ready.pop_front() {
+                    return Some((Ok(t), state));
+                }
+                if let Err(e) = state.poll().await {
+                    return Some((Err(e), state));
+                }
+            }
+        });
+        Poller {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+impl<T> Stream for Poller<T> {
+    type Item = anyhow::Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl<T> Debug for Poller<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poller").finish_non_exhaustive()
+    }
+}
+
+impl<R> State<R>
+where
+    R: RangeRequest,
+    R::Envelope: Envelope<Item = R::Item>,
+{
+    /// Polls the timeline once and moves the statuses that cannot be preceded by any later
+    /// status to `ready`.
+    async fn poll(&mut self) -> anyhow::Result<()> {
+        self.interval.tick().await;
+
+        let transport = match self.transport {
+            Some(ref mut transport) => transport,
+            None => {
+                let transport =
+                    transport::Http2::connect(self.request.host(), util::HTTPS_DEFAULT_PORT)
+                        .await?;
+                self.transport.insert(Box::new(transport))
+            }
+        };
+        if self.broken {
+            transport.reconnect().await?;
+            self.broken = false;
+        }
+
+        let since_id = self.latest.map(|(latest_id, retrieved_ms)| {
+            self.policy
+                .since_id(&R::Item::LAYOUT, latest_id, retrieved_ms)
+        });
+        if since_id.is_some() {
+            self.request.set_since_id(since_id);
+        }
+        let retrieved_ms = util::time_to_unix_ms(SystemTime::now());
+        let result = self
+            .request
+            .fetch(
+                util::DeserializeIntoVec(&mut self.timeline),
+                &self.token,
+                &mut **transport,
+            )
+            .await;
+        // A full page may have left out some of the statuses since `since_id`, which would be
+        // skipped for good once `since_id` moves past them.
+        let truncated = since_id.is_some() && self.timeline.len() >= self.request.limit();
+        let result = match result {
+            Ok(()) if truncated => {
+                let ids = self.timeline.iter().map(|t| t.id());
+                let (since_id, max_id) = if R::OLDEST_FIRST {
+                    (ids.max(), None)
+                } else {
+                    (since_id, ids.min().and_then(|id| id.checked_sub(1)))
+                };
+                api::fetch_range(
+                    &self.request,
+                    since_id,
+                    max_id,
+                    &self.token,
+                    &mut **transport,
+                )
+                .await
+                .map(|rest| self.timeline.extend(rest))
+            }
+            result => result,
+        };
+        if let Err(cause) = result {
+            if cause.is::<hyper::Error>() {
+                // Reconnect on the next poll.
+                self.broken = true;
+            }
+            return Err(cause);
+        }
+
+        let latest_id = self
+            .timeline
+            .iter()
+            .map(|t| t.id())
+            .chain(self.latest.map(|(latest_id, _)| latest_id))
+            .max();
+        let Some(latest_id) = latest_id else {
+            return Ok(());
+        };
+        self.latest = Some((latest_id, retrieved_ms));
+
+        for t in self.timeline.drain(..) {
+            if self.dedup.insert(t.id()) {
+                self.pending.insert(t.id(), t);
+            }
+        }
+        self.dedup
+            .expire(&self.policy.lower(&R::Item::LAYOUT, latest_id));
+
+        // The next request is made with this `since_id`, so nothing at or below it can appear
+        // anymore.
+        let since_id = self
+            .policy
+            .since_id(&R::Item::LAYOUT, latest_id, retrieved_ms);
+        let rest = self.pending.split_off(&(since_id + 1));
+        self.ready
+            .extend(mem::replace(&mut self.pending, rest).into_values());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use futures_util::StreamExt;
+    use hyper::Response;
+
+    use crate::api::mastodon::{Status, Statuses, Timeline};
+    use crate::snowflake::Layout;
+    use crate::transport::Mock;
+
+    use super::*;
+
+    const K_MS: u64 = 200;
+
+    /// Returns a `Poller` of a timeline served from `statuses`, where `update` is called with
+    /// the number of the request and its `since_id` to add statuses before each response.
+    fn poller(
+        statuses: Arc<Mutex<BTreeSet<u64>>>,
+        mut update: impl FnMut(u64, Option<u64>, &mut BTreeSet<u64>) + Send + 'static,
+    ) -> Poller<Status> {
+        let mut nth = 0;
+        let transport = Mock::new(move |request| {
+            let mut since_id = None;
+            let mut max_id = None;
+            let mut limit = usize::MAX;
+            let query = request.uri().query().unwrap_or_default();
+            for (name, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+                match name {
+                    "since_id" => since_id = Some(value.parse()?),
+                    "max_id" => max_id = Some(value.parse()?),
+                    "limit" => limit = value.parse()?,
+                    _ => {}
+                }
+            }
+            let mut statuses = statuses.lock().unwrap();
+            update(nth, since_id, &mut statuses);
+            nth += 1;
+            let page: Vec<_> = statuses
+                .iter()
+                .rev()
+                .filter(|&&id| since_id.is_none_or(|s| id > s) && max_id.is_none_or(|m| id < m))
+                .take(limit)
+                .map(|id| serde_json::json!({ "id": id.to_string(), "account": { "id": "1" } }))
+                .collect();
+            Ok(Response::new(Bytes::from(serde_json::to_vec(&page)?)))
+        });
+        let request = Statuses::new("mastodon.example".to_owned(), Timeline::Home);
+        let token = api::Token::from_bearer("token").unwrap();
+        Poller::with_transport(request, transport, token, K_MS, Duration::from_millis(10))
+    }
+
+    fn id_ms_ago(ms: u64) -> u64 {
+        let now_ms = util::time_to_unix_ms(SystemTime::now());
+        Layout::MASTODON.min_id_at(now_ms - ms).unwrap()
+    }
+
+    /// Asserts that `poller` yields each of `statuses` exactly once and in ascending ID order.
+    async fn assert_yields_all(mut poller: Poller<Status>, statuses: &Mutex<BTreeSet<u64>>) {
+        let mut yielded = Vec::new();
+        loop {
+            let next = tokio::time::timeout(Duration::from_millis(10 * K_MS), poller.next()).await;
+            let Ok(next) = next else {
+                break;
+            };
+            yielded.push(next.unwrap().unwrap().id);
+        }
+        assert!(yielded.windows(2).all(|w| w[0] < w[1]), "{:?}", yielded);
+        let statuses = statuses.lock().unwrap();
+        assert_eq!(yielded, statuses.iter().copied().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn late_status() {
+        let statuses = Arc::new(Mutex::new(BTreeSet::from([
+            id_ms_ago(3000),
+            id_ms_ago(2000),
+            id_ms_ago(1000),
+        ])));
+        let poller = poller(statuses.clone(), |nth, since_id, statuses| {
+            if nth == 2 {
+                // A status that appears after a newer one, but within `k_ms` of its timestamp.
+                statuses.insert(since_id.unwrap() + 1);
+                statuses.insert(id_ms_ago(0));
+            }
+        });
+        assert_yields_all(poller, &statuses).await;
+    }
+
+    #[tokio::test]
+    async fn truncated_page() {
+        let statuses = Arc::new(Mutex::new(BTreeSet::from([id_ms_ago(1000)])));
+        let poller = poller(statuses.clone(), |nth, since_id, statuses| {
+            if nth == 2 {
+                // More statuses than a page holds, right above `since_id` so that the next
+                // `since_id` moves past the ones left out of the page.
+                let since_id = since_id.unwrap();
+                statuses.extend(since_id + 1..=since_id + 100);
+            }
+        });
+        assert_yields_all(poller, &statuses).await;
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsString;
+use std::marker::PhantomData;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use getopts::Options;
+use tokio::sync::mpsc;
+
+use crate::api::lists::{self, List};
+use crate::api::stream::{self, Endpoint};
+use crate::api::{self, Request};
+use crate::transport::{self, Transport};
+use crate::util;
+
+/// Maximum number of members of a List.
+const MAX_LIST_LENGTH: usize = 5000;
+/// Interval between the requests to add members.
+const INTERVAL: Duration = Duration::from_secs(1);
+/// Length of a rate limit window, which is waited for if the API does not tell the reset time.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+pub async fn main(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    opts.optopt(
+        "",
+        "credentials",
+        "path to API credentials file (default: reads from `$HOME/.twurlrc` if any)",
+        "FILE",
+    );
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let list_id: u64 = if let [ref s] = *matches.free {
+        s.parse()?
+    } else {
+        println!("{}: missing LIST_ID argument", program);
+        print_usage(&program, &opts);
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let Some((token, bearer)) = crate::load_credentials(matches.opt_str("credentials"))? else {
+        println!("{}: missing `--credential` option and `.twurlrc`", program);
+        print_usage(&program, &opts);
+        return Ok(ExitCode::FAILURE);
+    };
+    let token = api::Token::from(token);
+    let Some(bearer) = bearer.as_deref().and_then(api::Token::from_bearer) else {
+        anyhow::bail!("the sampled stream requires a bearer token in the credentials");
+    };
+
+    populate(list_id, &token, bearer).await?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} populate [OPTIONS..] LIST_ID", program);
+    print!("{}", opts.usage(&brief));
+    println!(
+        "\nAdds the authors of the statuses from the sampled stream to the List until it has \
+        {} members.\nThe existing members are kept, so the command can be resumed at any time.",
+        MAX_LIST_LENGTH
+    );
+}
+
+async fn populate(list_id: u64, token: &api::Token, bearer: api::Token) -> anyhow::Result<()> {
+    let mut transport = transport::Http2::connect(api::HOST, util::HTTPS_DEFAULT_PORT).await?;
+
+    let users = api::fetch_pages(&lists::Members::new(list_id), token, &mut transport).await?;
+    let mut member_count = users.len();
+    // The users who are either in the List or have been tried to be added.
+    let mut known: HashSet<u64> = users.iter().map(|u| u.id).collect();
+    tracing::info!(member_count, "Retrieved the List members");
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let stream = tokio::spawn(stream::forward(Endpoint::Sample, bearer, None, sender));
+
+    // The candidates, the most recently active of which are added first.
+    let mut candidates = VecDeque::new();
+    let mut rejection_count = 0;
+    let mut interval = tokio::time::interval(INTERVAL);
+    while member_count < MAX_LIST_LENGTH {
+        interval.tick().await;
+
+        let remaining = MAX_LIST_LENGTH - member_count;
+        loop {
+            while let Ok((tweet, _)) = receiver.try_recv() {
+                push_candidate(&mut candidates, &known, tweet.author_id, remaining);
+            }
+            if !candidates.is_empty() {
+                break;
+            }
+            let Some((tweet, _)) = receiver.recv().await else {
+                anyhow::bail!("the stream has stopped");
+            };
+            push_candidate(&mut candidates, &known, tweet.author_id, remaining);
+        }
+
+        let len = candidates
+            .len()
+            .min(remaining)
+            .min(lists::MAX_MEMBERS_PER_REQUEST);
+        let batch: Vec<u64> = candidates.drain(candidates.len() - len..).collect();
+        known.extend(&batch);
+
+        let result = lists::MembersCreateAll::new(list_id, batch.clone())
+            .fetch(PhantomData::<List>, token, &mut transport)
+            .await;
+        match result {
+            Ok(list) => {
+                tracing::info!(
+                    users = ?batch,
+                    member_count = list.member_count,
+                    "Added to the List"
+                );
+                member_count = list.member_count;
+                rejection_count = 0;
+            }
+            Err(cause) if cause.is::<api::RateLimitError>() => {
+                let reset = cause.downcast_ref::<api::RateLimitError>().unwrap().reset;
+                let now = util::time_to_unix(SystemTime::now());
+                let wait = reset.map_or(RATE_LIMIT_WINDOW, |reset| {
+                    Duration::from_secs(reset).saturating_sub(now)
+                });
+                // Dropping the batch as we're going to have a bunch of new users anyway.
+                tracing::info!(?wait, "Rate limit exceeded; will retry later");
+                tokio::time::sleep(wait).await;
+            }
+            Err(cause)
+                if cause
+                    .downcast_ref::<api::StatusError>()
+                    .is_some_and(|e| e.0 == hyper::StatusCode::FORBIDDEN) =>
+            {
+                // Twitter seems to reject bulk additions to a List with 403.
+                let wait = Duration::from_secs(1 << rejection_count.min(10));
+                tracing::error!(%cause, ?wait, "Unable to add users to the List");
+                tokio::time::sleep(wait).await;
+                rejection_count += 1;
+            }
+            Err(cause) if cause.is::<hyper::Error>() => {
+                tracing::error!(%cause, "Error in HTTP connection");
+                transport.reconnect().await?;
+            }
+            Err(cause) => tracing::error!(%cause, "Unable to add users to the List"),
+        }
+    }
+
+    stream.abort();
+    println!("Collected {} users", member_count);
+
+    Ok(())
+}
+
+fn push_candidate(candidates: &mut VecDeque<u64>, known: &HashSet<u64>, user: u64, cap: usize) {
+    if known.contains(&user) || candidates.contains(&user) {
+        return;
+    }
+    candidates.push_back(user);
+    // Prevent the queue from having excessive number of elements while waiting.
+    if candidates.len() > cap {
+        candidates.pop_front();
+    }
+}
@@ -1,16 +1,36 @@
+mod compare;
+mod history;
+mod latency;
+mod probe;
+mod stream;
+mod vanished;
+mod workers;
+
+use std::collections::HashSet;
 use std::fmt::Debug;
+use std::fs::File;
 use std::io::{stdout, Write};
 use std::marker::PhantomData;
 use std::mem;
 use std::ops::ControlFlow;
 use std::time::{Duration, Instant, SystemTime};
 
-use bytes::Bytes;
-use http_body_util::Empty;
-use hyper::client::conn::http2::SendRequest;
+use leaky_snowflake_observer::dedup::Dedup;
+use leaky_snowflake_observer::poller::SinceIdPolicy;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use tokio::task::JoinHandle;
+
+use crate::api::{self, TimelineItem, TimelineRequest};
+use crate::transport::{self, Transport};
+use crate::{capture, util};
 
-use crate::api::{self, TimelineRequest, Tweet};
-use crate::util::{self, SliceIsSortedExt};
+use self::history::{Generation, History, Leak, Truncated};
+use self::latency::Latency;
+use self::probe::Probe;
+use self::stream::GroundTruth;
+use self::vanished::Prober;
+use self::workers::Workers;
 
 const MAX_TIMELINE_LEN: usize = 200;
 const INTERVAL: Duration = Duration::from_secs(1);
@@ -18,105 +38,168 @@ const INTERVAL: Duration = Duration::from_secs(1);
 pub struct Args<R> {
     pub request: R,
     pub k_ms: u64,
+    pub history_len: usize,
+    pub latency_window: usize,
+    pub latency_output: Option<File>,
+    pub vanished_output: Option<File>,
+    pub workers_output: Option<File>,
+    pub stream: Option<StreamArgs>,
+    pub compare: bool,
+    pub probe: bool,
+    pub full: bool,
+    pub keep_going: bool,
+    pub capture_output: Option<File>,
+    /// Whether to connect to the API over HTTP/1.1 instead of HTTP/2.
+    pub http1: bool,
     pub token: api::Token,
 }
 
-#[tracing::instrument(skip(token))]
+/// Options for the comparison against the filtered stream.
+pub struct StreamArgs {
+    pub members: api::lists::Members,
+    /// The app-only token for the stream.
+    pub token: api::Token,
+    /// Time allowed for a streamed status to appear in the timeline.
+    pub budget_ms: u64,
+    pub output: File,
+}
+
+struct Observer<R: TimelineRequest> {
+    request: R,
+    k_ms: u64,
+    token: api::Token,
+    start_ms: u64,
+    history: History<R::Item>,
+    /// The IDs seen in the recent polls, expired by the lower bound of `since_id`.
+    seen: Dedup,
+    latency: Latency,
+    workers: Workers,
+    ground_truth: Option<GroundTruth>,
+    prober: Prober,
+    /// The bisections of the `since_id` values in progress.
+    probes: Vec<JoinHandle<()>>,
+    probe: bool,
+    /// The `since_id` policy, or `None` to retrieve the whole timeline every time.
+    policy: Option<SinceIdPolicy>,
+    keep_going: bool,
+    http1: bool,
+    capture: Option<capture::Writer>,
+    timeline: Vec<R::Item>,
+    transport: Box<dyn Transport>,
+}
+
+#[tracing::instrument(skip(token, stream))]
 pub async fn run<R>(
     Args {
-        mut request,
+        request,
         k_ms,
+        history_len,
+        latency_window,
+        latency_output,
+        vanished_output,
+        workers_output,
+        stream,
+        compare,
+        probe,
+        full,
+        keep_going,
+        capture_output,
+        http1,
         token,
     }: Args<R>,
 ) -> anyhow::Result<()>
 where
-    R: Debug + TimelineRequest,
+    R: Clone + Debug + Serialize + TimelineRequest + Send + 'static,
+    R::Item: Send,
 {
+    if compare {
+        return compare::run(request, k_ms, http1, token).await;
+    }
+
     let mut nth = 1;
-    let mut request_sender = util::http2_connect(api::HOST, util::HTTPS_DEFAULT_PORT).await?;
-
-    let (start_ms, mut interval) = {
-        // Start the interval at exactly the beginning of a second of the clock
-        // to make the output a bit cleaner and maybe to make the rate-limit
-        // behavior and the experiment condition more consistent (e.g. speed
-        // of the TL might be biased by subsecond values of the clock).
-        let now_sys = SystemTime::now();
-        let now = Instant::now();
-        let now_unix = util::time_to_unix(now_sys);
-        let now_subsec = now_unix - Duration::from_secs(now_unix.as_secs());
-        let wait = Duration::from_secs(1) - now_subsec;
-        let start = now + wait;
-        let start_ms = util::unix_to_ms(now_unix + wait);
-        (start_ms, tokio::time::interval_at(start.into(), INTERVAL))
+    let mut transport = transport::connect(request.host(), util::HTTPS_DEFAULT_PORT, http1).await?;
+    let ground_truth = if let Some(stream) = stream {
+        Some(GroundTruth::start(stream, &token, &mut *transport).await?)
+    } else {
+        None
     };
+    let prober = Prober::start(request.host(), http1, token.clone(), vanished_output);
+    let (start_ms, mut interval) = start_interval();
 
-    let mut timeline = Vec::with_capacity(MAX_TIMELINE_LEN);
-    let mut previous_state: Option<State> = None;
+    let mut observer = Observer {
+        request,
+        k_ms,
+        token,
+        start_ms,
+        history: History::new(history_len),
+        seen: Dedup::new(),
+        latency: Latency::new(latency_window, latency_output),
+        workers: Workers::new(R::Item::LAYOUT, workers_output),
+        ground_truth,
+        prober,
+        probes: Vec::new(),
+        probe,
+        policy: (!full).then_some(SinceIdPolicy::Adjusted { k_ms }),
+        keep_going,
+        http1,
+        capture: capture_output.map(capture::Writer::new),
+        timeline: Vec::with_capacity(MAX_TIMELINE_LEN),
+        transport,
+    };
     loop {
         interval.tick().await;
-        if let ControlFlow::Break(()) = poll_timeline(
-            &mut request,
-            k_ms,
-            &token,
-            start_ms,
-            nth,
-            &mut previous_state,
-            &mut timeline,
-            &mut request_sender,
-        )
-        .await?
-        {
+        if let ControlFlow::Break(()) = observer.poll_timeline(nth).await? {
             break;
         }
         nth += 1;
     }
+    observer.finish().await;
 
     Ok(())
 }
 
-struct State {
-    timeline: Vec<Tweet>,
-    latest_id: u64,
-    retrieved_ms: u64,
+/// Returns an interval ticking every `INTERVAL` and its start time in Unix milliseconds.
+fn start_interval() -> (u64, tokio::time::Interval) {
+    // Start the interval at exactly the beginning of a second of the clock
+    // to make the output a bit cleaner and maybe to make the rate-limit
+    // behavior and the experiment condition more consistent (e.g. speed
+    // of the TL might be biased by subsecond values of the clock).
+    let now_sys = SystemTime::now();
+    let now = Instant::now();
+    let now_unix = util::time_to_unix(now_sys);
+    let now_subsec = now_unix - Duration::from_secs(now_unix.as_secs());
+    let wait = Duration::from_secs(1) - now_subsec;
+    let start = now + wait;
+    let start_ms = util::unix_to_ms(now_unix + wait);
+    (start_ms, tokio::time::interval_at(start.into(), INTERVAL))
 }
 
-impl State {
-    fn next_since_id(&self, k_ms: u64) -> u64 {
-        let lower = (((self.latest_id >> 22) - k_ms) << 22) - 1;
-        (util::unix_ms_to_sf(self.retrieved_ms - k_ms) - 1).clamp(lower, self.latest_id)
-    }
-}
-
-#[tracing::instrument(skip_all, fields(nth, latest_id = previous_state.as_ref().map(|s| s.latest_id)))]
-async fn poll_timeline<R>(
-    request: &mut R,
-    k_ms: u64,
+/// Retrieves the timeline into `timeline`, sorted in reverse ID order.
+///
+/// Returns the times the request was initiated and completed at, in Unix milliseconds,
+/// or `None` if the request has failed in a recoverable manner.
+async fn fetch_timeline<R>(
+    request: &R,
+    timeline: &mut Vec<R::Item>,
     token: &api::Token,
-    start_ms: u64,
-    nth: u64,
-    previous_state: &mut Option<State>,
-    timeline: &mut Vec<Tweet>,
-    request_sender: &mut SendRequest<Empty<Bytes>>,
-) -> anyhow::Result<ControlFlow<()>>
+    transport: &mut dyn Transport,
+) -> anyhow::Result<Option<(u64, u64)>>
 where
     R: Debug + TimelineRequest,
 {
-    if let Some(ref previous) = *previous_state {
-        request.set_since_id(Some(previous.next_since_id(k_ms)));
-    }
-
     let retrieved_ms = util::time_to_unix_ms(SystemTime::now());
     tracing::info!(?request, %retrieved_ms, "Initiating API request");
     let result = request
-        .fetch(util::DeserializeIntoVec(timeline), &token, request_sender)
+        .fetch(util::DeserializeIntoVec(timeline), token, transport)
         .await;
+    let received_ms = util::time_to_unix_ms(SystemTime::now());
     match result {
         Ok(()) => tracing::info!(?timeline, "Request succeeded"),
         Err(cause) if cause.is::<hyper::Error>() => {
             tracing::error!(%cause, "Error in HTTP connection");
             // Attempt to reconnect
-            *request_sender = util::http2_connect(api::HOST, util::HTTPS_DEFAULT_PORT).await?;
-            return Ok(ControlFlow::Continue(()));
+            transport.reconnect().await?;
+            return Ok(None);
         }
         Err(cause) if cause.is::<serde_json::Error>() => {
             tracing::error!("Twitter responded with unexpected format");
@@ -124,65 +207,181 @@ where
         }
         Err(cause) => {
             tracing::error!(%cause, "Error in API request");
-            return Ok(ControlFlow::Continue(()));
+            return Ok(None);
         }
     };
 
     // Make sure the TL is sorted in reverse chronological order, just in case.
     // ... Well, reverse Snowflake ID order, I mean.
-    #[allow(unstable_name_collisions)]
-    if !timeline.is_sorted_by(|t, u| Some(t.cmp_rev_id(u))) {
+    if !timeline.is_sorted_by(|t, u| t.cmp_rev_id(u).is_le()) {
         tracing::warn!("response is not sorted");
-        timeline.sort_unstable_by(Tweet::cmp_rev_id);
+        timeline.sort_unstable_by(R::Item::cmp_rev_id);
     }
 
-    if let Some(ref previous) = *previous_state {
-        // Check if we've missed any Tweets in the previous request
-        // whose ID is less than the largest one retrieved before.
-
-        // First, slice the timelines so that they only contain IDs in the range
-        // of `(since_id, latest_id]`. Note that the ordering of the timelines
-        // and hence the slicing ranges are reversed ones.
-        let since_id = previous.next_since_id(k_ms);
-        let old = {
-            let seek = since_id;
-            let i = previous
-                .timeline
-                .binary_search_by(move |t| seek.cmp(&t.id))
-                .unwrap_or_else(|i| i);
-            &previous.timeline[..i]
-        };
-        let new = {
-            let seek = previous.latest_id;
-            let i = timeline
-                .binary_search_by(move |t| seek.cmp(&t.id))
-                .unwrap_or_else(|i| i);
-            &timeline[i..]
+    Ok(Some((retrieved_ms, received_ms)))
+}
+
+impl<R> Args<R> {
+    /// Replaces the request, e.g. the placeholder set while parsing the command line.
+    pub fn with_request<S>(self, request: S) -> Args<S> {
+        Args {
+            request,
+            k_ms: self.k_ms,
+            history_len: self.history_len,
+            latency_window: self.latency_window,
+            latency_output: self.latency_output,
+            vanished_output: self.vanished_output,
+            workers_output: self.workers_output,
+            stream: self.stream,
+            compare: self.compare,
+            probe: self.probe,
+            full: self.full,
+            keep_going: self.keep_going,
+            capture_output: self.capture_output,
+            http1: self.http1,
+            token: self.token,
+        }
+    }
+}
+
+impl<R> Observer<R>
+where
+    R: Clone + Debug + Serialize + TimelineRequest + Send + 'static,
+    R::Item: Send,
+{
+    #[tracing::instrument(skip_all, fields(nth, latest_id = self.history.latest().map(|g| g.latest_id)))]
+    async fn poll_timeline(&mut self, nth: u64) -> anyhow::Result<ControlFlow<()>> {
+        let since_id = self.policy.and_then(|policy| {
+            let g = self.history.latest()?;
+            Some(policy.since_id(&R::Item::LAYOUT, g.latest_id, g.retrieved_ms))
+        });
+        // Set it even if it is `None`, which overwrites the `since_id` of the "magic" check of
+        // an earlier leak.
+        self.request.set_since_id(since_id);
+        // The effective parameters of the request, to be logged in the output records.
+        let request = serde_json::to_value(&self.request)?;
+
+        let (retrieved_ms, received_ms) = if let Some(times) = fetch_timeline(
+            &self.request,
+            &mut self.timeline,
+            &self.token,
+            &mut *self.transport,
+        )
+        .await?
+        {
+            times
+        } else {
+            return Ok(ControlFlow::Continue(()));
         };
+        let timeline = &self.timeline;
+        let truncated = (timeline.len() >= self.request.limit()).then_some(if R::OLDEST_FIRST {
+            Truncated::Newest
+        } else {
+            Truncated::Oldest
+        });
 
-        tracing::debug!(
-            ?new,
-            ?old,
-            "Comparing the overlapping part of the timelines..."
-        );
+        if let Some(ref mut capture) = self.capture {
+            capture.write(&capture::Poll {
+                nth,
+                retrieved_ms,
+                since_id,
+                truncated: truncated.is_some(),
+                ids: timeline.iter().map(|t| t.id()).collect(),
+                request: Some(request.clone()),
+            })?;
+        }
+
+        if let Some(ref mut ground_truth) = self.ground_truth {
+            ground_truth.record(nth, &request, timeline, retrieved_ms)?;
+        }
+
+        // The statuses in the first response have been visible for an unknown time, so they are
+        // only marked as seen rather than taken as newly visible.
+        let first_poll = self.history.latest().is_none();
+        let seen = &mut self.seen;
+        let mut new: HashSet<u64> = timeline
+            .iter()
+            .map(|t| t.id())
+            .filter(|&id| seen.insert(id))
+            .collect();
+        if first_poll {
+            new.clear();
+        }
+        let is_new = |t: &&R::Item| new.contains(&t.id());
+        for t in timeline.iter().filter(is_new) {
+            if t.is_created_at_consistent() == Some(false) {
+                tracing::warn!(
+                    id = %t.id(),
+                    kind = ?t.kind(),
+                    "ID timestamp is inconsistent with `created_at`"
+                );
+            }
+        }
+        self.latency.record(
+            nth,
+            &request,
+            timeline.iter().filter(is_new),
+            retrieved_ms,
+            received_ms,
+        )?;
+
+        // Check if any of the statuses we've seen in the previous request has disappeared,
+        // so that deletions and visibility changes are not mistaken for anything else.
+        if let Some(previous) = self.history.latest() {
+            let vanished = vanished::diff(previous, timeline, since_id, truncated);
+            if !vanished.is_empty() {
+                self.prober.probe(nth, retrieved_ms, &request, &vanished);
+            }
+        }
+        let timeline = &mut self.timeline;
+
+        // Check if we've missed any Tweets in the earlier requests whose ID is less than
+        // the largest one retrieved before. Looking back over multiple generations lets us
+        // catch the statuses whose visibility is delayed by more than one polling interval.
+        let leaks = self
+            .history
+            .classify(timeline.iter().filter(is_new), retrieved_ms);
+
+        let latest_id = self.history.latest().map(|g| g.latest_id);
+        self.workers.record(
+            nth,
+            timeline.iter().filter(is_new),
+            &leaks,
+            latest_id,
+            retrieved_ms,
+            received_ms,
+        )?;
 
-        // Next, search for a "leaked" Tweet...
-        if let Some(leaked) = util::first_diff_sorted_by(new, old, |t, u| t.cmp_rev_id(u)) {
+        if let Some(leaked) = leaks.first() {
             // Gotcha!
-            tracing::info!(id = %leaked.id, "Observed a leaked status");
+            for leak in &leaks {
+                tracing::info!(
+                    id = %leak.id,
+                    missed_polls = leak.missed_polls,
+                    delay_ms = leak.delay_ms,
+                    "Observed a leaked status"
+                );
+            }
 
-            let magic = if since_id == previous.latest_id {
+            let previous = self.history.latest().unwrap();
+
+            let magic = if since_id == Some(previous.latest_id) {
                 Some(true)
             } else {
                 tracing::info!("Checking if the \"magic\" exists");
-                request.set_since_id(Some(previous.latest_id));
-                let result = request
-                    .fetch(PhantomData::<Vec<Tweet>>, &token, request_sender)
+                self.request.set_since_id(Some(previous.latest_id));
+                let result = self
+                    .request
+                    .fetch(
+                        PhantomData::<Vec<R::Item>>,
+                        &self.token,
+                        &mut *self.transport,
+                    )
                     .await;
                 match result {
                     Ok(timeline) => {
                         tracing::info!(?timeline, "Request succeeded");
-                        Some(timeline.iter().any(|t| t.id == leaked.id))
+                        Some(timeline.iter().any(|t| t.id() == leaked.id))
                     }
                     Err(cause) => {
                         // We're out of luck...
@@ -194,65 +393,263 @@ where
 
             // Now, report the results and call it a day.
             #[derive(serde::Serialize)]
-            struct Output<'a> {
+            struct Output {
                 k_ms: u64,
                 start_ms: u64,
                 nth: u64,
-                previous: Previous<'a>,
-                latest: Latest<'a>,
+                request: serde_json::Value,
+                previous: Box<RawValue>,
+                latest: Box<RawValue>,
+                leaks: Vec<Leak>,
                 magic: Option<bool>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                probe: Option<Probe>,
             }
             #[derive(serde::Serialize)]
-            struct Previous<'a> {
+            struct Previous<'a, T> {
                 retrieved_ms: u64,
                 latest_id: u64,
-                statuses: &'a [Tweet],
+                statuses: &'a [T],
             }
             #[derive(serde::Serialize)]
-            struct Latest<'a> {
+            struct Latest<'a, T> {
                 retrieved_ms: u64,
-                statuses: &'a [Tweet],
+                statuses: &'a [T],
+            }
+            fn print(output: &Output) -> anyhow::Result<()> {
+                let mut stdout = stdout().lock();
+                serde_json::to_writer(&mut stdout, output)?;
+                writeln!(stdout)?;
+                Ok(())
             }
-            let output = Output {
-                k_ms,
-                start_ms,
+
+            let leaked_id = leaked.id;
+            let mut output = Output {
+                k_ms: self.k_ms,
+                start_ms: self.start_ms,
                 nth,
-                previous: Previous {
+                request: request.clone(),
+                previous: serde_json::value::to_raw_value(&Previous {
                     retrieved_ms: previous.retrieved_ms,
                     latest_id: previous.latest_id,
                     statuses: &previous.timeline,
-                },
-                latest: Latest {
+                })?,
+                latest: serde_json::value::to_raw_value(&Latest {
                     retrieved_ms,
-                    statuses: &timeline,
-                },
+                    statuses: timeline,
+                })?,
+                leaks,
                 magic,
+                probe: None,
             };
 
-            let mut stdout = stdout().lock();
-            serde_json::to_writer(&mut stdout, &output)?;
-            writeln!(stdout).unwrap();
+            // Without `since_id` (`--full`), there is no range of `since_id` values to bisect.
+            if let (true, Some(since_id)) = (self.probe, since_id) {
+                // The bisection takes minutes, so it is made in the background with its own
+                // connection lest the timeline goes unobserved meanwhile.
+                let mut request = self.request.clone();
+                let latest_id = previous.latest_id;
+                let token = self.token.clone();
+                let http1 = self.http1;
+                self.probes.retain(|task| !task.is_finished());
+                self.probes.push(tokio::spawn(async move {
+                    tracing::info!("Probing how the server filters by `since_id`");
+                    let result = async {
+                        let mut transport =
+                            transport::connect(request.host(), util::HTTPS_DEFAULT_PORT, http1)
+                                .await?;
+                        let probe = probe::bisect(
+                            &mut request,
+                            leaked_id,
+                            since_id,
+                            latest_id,
+                            &token,
+                            &mut *transport,
+                        )
+                        .await;
+                        output.probe = Some(probe);
+                        print(&output)
+                    }
+                    .await;
+                    if let Err(cause) = result {
+                        tracing::error!(?cause, "Error while probing the leak");
+                    }
+                }));
+            } else {
+                print(&output)?;
+            }
 
-            return Ok(ControlFlow::Break(()));
+            if !self.keep_going {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+
+        let latest_id = match (self.history.latest(), timeline.first()) {
+            (Some(previous), Some(t)) => previous.latest_id.max(t.id()),
+            (Some(previous), None) => previous.latest_id,
+            (None, Some(t)) => t.id(),
+            (None, None) => return Ok(ControlFlow::Continue(())),
+        };
+        let storage = Vec::with_capacity(MAX_TIMELINE_LEN);
+        let generation = Generation {
+            nth,
+            retrieved_ms,
+            since_id,
+            latest_id,
+            timeline: mem::replace(timeline, storage),
+            truncated,
+        };
+        if let Some(mut storage) = self.history.push(generation) {
+            storage.clear();
+            self.timeline = storage;
+        }
+
+        // Forget the IDs that cannot be returned again.
+        match self.policy {
+            Some(policy) => self.seen.expire(&policy.lower(&R::Item::LAYOUT, latest_id)),
+            None => {
+                // Without `since_id`, a full response only covers the latest statuses.
+                let latest = self.history.latest().unwrap();
+                if let (Some(Truncated::Oldest), Some(t)) =
+                    (latest.truncated, latest.timeline.last())
+                {
+                    self.seen.expire(&(t.id() - 1));
+                }
+            }
         }
+
+        Ok(ControlFlow::Continue(()))
     }
 
-    if let Some(ref mut previous) = *previous_state {
-        match *previous.timeline {
-            [ref t, ..] if t.id > previous.latest_id => {
-                previous.latest_id = t.id;
+    /// Waits for the lookups and the probes in the background to complete.
+    async fn finish(self) {
+        self.prober.finish().await;
+        for task in self.probes {
+            if let Err(cause) = task.await {
+                tracing::error!(?cause, "The probe has panicked");
             }
-            _ => {}
         }
-        previous.retrieved_ms = retrieved_ms;
-        mem::swap(&mut previous.timeline, timeline);
-    } else if timeline.len() > 0 {
-        *previous_state = Some(State {
-            latest_id: timeline[0].id,
-            timeline: mem::replace(timeline, Vec::with_capacity(MAX_TIMELINE_LEN)),
-            retrieved_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use hyper::Response;
+    use leaky_snowflake_observer::snowflake::Layout;
+
+    use crate::api::mastodon::{Statuses, Timeline};
+    use crate::transport::Mock;
+
+    use super::*;
+
+    const K_MS: u64 = 1000;
+
+    /// Returns an `Observer` of a timeline served from `statuses`, retrieving the whole
+    /// timeline every time if `full` is `true`.
+    fn observer(statuses: Arc<Mutex<BTreeSet<u64>>>, full: bool) -> Observer<Statuses> {
+        let transport = Mock::new(move |request| {
+            let mut since_id = None;
+            let mut limit = usize::MAX;
+            let query = request.uri().query().unwrap_or_default();
+            for (name, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+                match name {
+                    "since_id" => since_id = Some(value.parse()?),
+                    "limit" => limit = value.parse()?,
+                    _ => {}
+                }
+            }
+            let page: Vec<_> = statuses
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .filter(|&&id| since_id.is_none_or(|s| id > s))
+                .take(limit)
+                .map(|id| serde_json::json!({ "id": id.to_string(), "account": { "id": "1" } }))
+                .collect();
+            Ok(Response::new(Bytes::from(serde_json::to_vec(&page)?)))
         });
+        let host = "mastodon.example";
+        let token = api::Token::from_bearer("token").unwrap();
+        Observer {
+            request: Statuses::new(host.to_owned(), Timeline::Home),
+            k_ms: K_MS,
+            token: token.clone(),
+            start_ms: 0,
+            history: History::new(4),
+            seen: Dedup::new(),
+            latency: Latency::new(16, None),
+            workers: Workers::new(Layout::MASTODON, None),
+            ground_truth: None,
+            prober: Prober::start(host, false, token, None),
+            probes: Vec::new(),
+            probe: false,
+            policy: (!full).then_some(SinceIdPolicy::Adjusted { k_ms: K_MS }),
+            keep_going: false,
+            http1: false,
+            capture: None,
+            timeline: Vec::new(),
+            transport: Box::new(transport),
+        }
     }
 
-    Ok(ControlFlow::Continue(()))
+    fn id_ms_ago(ms: u64) -> u64 {
+        let now_ms = util::time_to_unix_ms(SystemTime::now());
+        Layout::MASTODON.min_id_at(now_ms - ms).unwrap()
+    }
+
+    #[tokio::test]
+    async fn leak() {
+        let statuses = Arc::new(Mutex::new(BTreeSet::from([
+            id_ms_ago(3 * K_MS),
+            id_ms_ago(K_MS / 2),
+        ])));
+        let mut observer = observer(statuses.clone(), false);
+        assert_eq!(
+            observer.poll_timeline(1).await.unwrap(),
+            ControlFlow::Continue(())
+        );
+
+        // A newer status is not a leak.
+        statuses.lock().unwrap().insert(id_ms_ago(0));
+        assert_eq!(
+            observer.poll_timeline(2).await.unwrap(),
+            ControlFlow::Continue(())
+        );
+
+        // A status appearing below the latest one that has been observed is.
+        let latest_id = observer.history.latest().unwrap().latest_id;
+        statuses.lock().unwrap().insert(latest_id - 1);
+        assert_eq!(
+            observer.poll_timeline(3).await.unwrap(),
+            ControlFlow::Break(())
+        );
+        observer.finish().await;
+    }
+
+    #[tokio::test]
+    async fn truncated_page() {
+        let ids = (0..50).map(|i| id_ms_ago(10 * K_MS) + i);
+        let statuses = Arc::new(Mutex::new(ids.collect::<BTreeSet<u64>>()));
+        let mut observer = observer(statuses.clone(), true);
+        assert_eq!(
+            observer.poll_timeline(1).await.unwrap(),
+            ControlFlow::Continue(())
+        );
+        let latest = observer.history.latest().unwrap();
+        assert!(matches!(latest.truncated, Some(Truncated::Oldest)));
+
+        // Deleting the newest status brings the one below the full page into the response, which
+        // is not a leak since the previous response could not have contained it.
+        statuses.lock().unwrap().pop_last();
+        assert_eq!(
+            observer.poll_timeline(2).await.unwrap(),
+            ControlFlow::Continue(())
+        );
+        observer.finish().await;
+    }
 }
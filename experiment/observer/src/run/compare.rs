@@ -0,0 +1,231 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Debug;
+use std::io::{stdout, Write};
+use std::mem;
+
+use serde::Serialize;
+
+use crate::api::{self, TimelineItem, TimelineRequest};
+use crate::transport::{self, Transport};
+use crate::util;
+
+use super::{SinceIdPolicy, MAX_TIMELINE_LEN};
+
+/// Number of the examples of lost statuses to be kept for the summary.
+const MAX_EXAMPLES: usize = 16;
+/// Number of polls between the summaries.
+const REPORT_INTERVAL: u64 = 60;
+
+/// A poller of the timeline with a specific `since_id` policy.
+struct Lane<R> {
+    policy: SinceIdPolicy,
+    request: R,
+    /// The highest ID delivered so far and the time of the last successful request.
+    latest: Option<(u64, u64)>,
+    /// The lowest ID that the first response has covered.
+    floor: Option<u64>,
+    /// IDs delivered by this lane that are yet to be compared against the other lane.
+    pending: BTreeSet<u64>,
+}
+
+#[derive(Default, Serialize)]
+struct Tally {
+    delivered_by_both: u64,
+    lost_by_naive: u64,
+    lost_by_adjusted: u64,
+}
+
+#[derive(Clone, Copy, Serialize)]
+struct Lost {
+    id: u64,
+    id_ms: u64,
+    /// The `since_id` value of the losing lane by the time the loss was determined.
+    since_id: u64,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Lost {
+        nth: u64,
+        request: &'a serde_json::Value,
+        lost_by: &'static str,
+        #[serde(flatten)]
+        status: Lost,
+    },
+    Summary {
+        k_ms: u64,
+        start_ms: u64,
+        nth: u64,
+        request: &'a serde_json::Value,
+        #[serde(flatten)]
+        tally: &'a Tally,
+        examples: &'a VecDeque<Lost>,
+    },
+}
+
+/// Polls the timeline alternately with the naive and the adjusted `since_id` policies and
+/// reports the statuses delivered by one of them but not the other.
+#[tracing::instrument(skip(token))]
+pub async fn run<R>(request: R, k_ms: u64, http1: bool, token: api::Token) -> anyhow::Result<()>
+where
+    R: Clone + Debug + Serialize + TimelineRequest,
+{
+    // The parameters common to both of the lanes, which only differ in `since_id`.
+    let params = serde_json::to_value(&request)?;
+    let mut transport = transport::connect(request.host(), util::HTTPS_DEFAULT_PORT, http1).await?;
+    let (start_ms, mut interval) = super::start_interval();
+
+    let mut naive = Lane::new(SinceIdPolicy::Naive, request.clone());
+    let mut adjusted = Lane::new(SinceIdPolicy::Adjusted { k_ms }, request);
+    let mut timeline = Vec::with_capacity(MAX_TIMELINE_LEN);
+    let mut tally = Tally::default();
+    let mut examples = VecDeque::with_capacity(MAX_EXAMPLES);
+
+    let mut stdout = stdout().lock();
+    let mut nth = 1;
+    loop {
+        interval.tick().await;
+
+        let lane = if nth % 2 == 1 {
+            &mut naive
+        } else {
+            &mut adjusted
+        };
+        lane.poll(&mut timeline, &token, &mut *transport).await?;
+
+        let (naive_resolved, adjusted_resolved) = match resolve(&mut naive, &mut adjusted) {
+            Some(resolved) => resolved,
+            None => {
+                nth += 1;
+                continue;
+            }
+        };
+
+        let mut report = |lost_by: &'static str, id: u64, since_id: u64| {
+            let status = Lost {
+                id,
+                id_ms: R::Item::LAYOUT.timestamp_ms(id),
+                since_id,
+            };
+            tracing::info!(id, lost_by, "Observed a status lost by a policy");
+            if examples.len() == MAX_EXAMPLES {
+                examples.pop_front();
+            }
+            examples.push_back(status);
+            let record = Record::Lost {
+                nth,
+                request: &params,
+                lost_by,
+                status,
+            };
+            serde_json::to_writer(&mut stdout, &record)?;
+            writeln!(stdout)?;
+            anyhow::Ok(())
+        };
+
+        let naive_since_id = naive.since_id().unwrap();
+        for &id in adjusted_resolved.difference(&naive_resolved) {
+            tally.lost_by_naive += 1;
+            report("naive", id, naive_since_id)?;
+        }
+        let adjusted_since_id = adjusted.since_id().unwrap();
+        for &id in naive_resolved.difference(&adjusted_resolved) {
+            tally.lost_by_adjusted += 1;
+            report("adjusted", id, adjusted_since_id)?;
+        }
+        tally.delivered_by_both += naive_resolved.intersection(&adjusted_resolved).count() as u64;
+
+        if nth.is_multiple_of(REPORT_INTERVAL) {
+            let record = Record::Summary {
+                k_ms,
+                start_ms,
+                nth,
+                request: &params,
+                tally: &tally,
+                examples: &examples,
+            };
+            serde_json::to_writer(&mut stdout, &record)?;
+            writeln!(stdout)?;
+        }
+        stdout.flush()?;
+
+        nth += 1;
+    }
+}
+
+/// Takes the IDs that neither of the lanes can deliver anymore out of their pending sets.
+fn resolve<R: TimelineRequest>(
+    naive: &mut Lane<R>,
+    adjusted: &mut Lane<R>,
+) -> Option<(BTreeSet<u64>, BTreeSet<u64>)> {
+    // A lane never delivers IDs lower than or equal to its `since_id`.
+    let horizon = naive.since_id()?.min(adjusted.since_id()?);
+    // Neither can we tell about the IDs below the range covered by the first responses.
+    let floor = naive.floor?.max(adjusted.floor?);
+    Some((
+        naive.resolve(horizon, floor),
+        adjusted.resolve(horizon, floor),
+    ))
+}
+
+impl<R: TimelineRequest> Lane<R> {
+    fn new(policy: SinceIdPolicy, request: R) -> Self {
+        Lane {
+            policy,
+            request,
+            latest: None,
+            floor: None,
+            pending: BTreeSet::new(),
+        }
+    }
+
+    fn since_id(&self) -> Option<u64> {
+        self.latest.map(|(latest_id, retrieved_ms)| {
+            self.policy
+                .since_id(&R::Item::LAYOUT, latest_id, retrieved_ms)
+        })
+    }
+
+    /// Removes and returns the pending IDs in the range of `[floor, horizon]`.
+    fn resolve(&mut self, horizon: u64, floor: u64) -> BTreeSet<u64> {
+        let rest = self.pending.split_off(&(horizon + 1));
+        let mut resolved = mem::replace(&mut self.pending, rest);
+        resolved.retain(|&id| id >= floor);
+        resolved
+    }
+}
+
+impl<R: Debug + TimelineRequest> Lane<R> {
+    #[tracing::instrument(skip_all, fields(policy = ?self.policy))]
+    async fn poll(
+        &mut self,
+        timeline: &mut Vec<R::Item>,
+        token: &api::Token,
+        transport: &mut dyn Transport,
+    ) -> anyhow::Result<()> {
+        if let Some(since_id) = self.since_id() {
+            self.request.set_since_id(Some(since_id));
+        }
+
+        let retrieved_ms =
+            match super::fetch_timeline(&self.request, timeline, token, transport).await? {
+                Some((retrieved_ms, _)) => retrieved_ms,
+                None => return Ok(()),
+            };
+
+        self.pending.extend(timeline.iter().map(|t| t.id()));
+        if self.floor.is_none() {
+            self.floor = timeline.last().map(|t| t.id());
+        }
+        let latest_id = match (self.latest, timeline.first()) {
+            (Some((latest_id, _)), Some(t)) => latest_id.max(t.id()),
+            (Some((latest_id, _)), None) => latest_id,
+            (None, Some(t)) => t.id(),
+            (None, None) => return Ok(()),
+        };
+        self.latest = Some((latest_id, retrieved_ms));
+
+        Ok(())
+    }
+}
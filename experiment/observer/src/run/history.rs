@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::api::{Kind, TimelineItem};
+
+/// A bounded ring of the timelines retrieved in the most recent polls.
+pub struct History<T> {
+    generations: VecDeque<Generation<T>>,
+    capacity: usize,
+}
+
+/// The result of a single poll of the timeline.
+pub struct Generation<T> {
+    pub nth: u64,
+    pub retrieved_ms: u64,
+    /// The `since_id` parameter of the request, if any.
+    pub since_id: Option<u64>,
+    /// The highest ID observed up to (and including) this poll.
+    pub latest_id: u64,
+    pub timeline: Vec<T>,
+    /// Set if the response was filled up to the limit of the request, in which case some of the
+    /// statuses may have been hidden by pagination.
+    pub truncated: Option<Truncated>,
+}
+
+/// The side of a full response where the statuses may have been hidden by pagination.
+#[derive(Clone, Copy, Debug)]
+pub enum Truncated {
+    /// The statuses lower than the last one in the timeline.
+    Oldest,
+    /// The statuses higher than the first one in the timeline, as with the requests that return
+    /// the oldest statuses first (`TimelineRequest::OLDEST_FIRST`).
+    Newest,
+}
+
+/// A newly seen status that should already have appeared in one or more of the earlier polls.
+#[derive(Debug, Serialize)]
+pub struct Leak {
+    pub id: u64,
+    /// The number of the polls whose response should have contained the status.
+    pub missed_polls: usize,
+    /// The sequence number of the first poll that should have contained the status.
+    pub first_missed_nth: u64,
+    /// The time elapsed between the first poll that should have contained the status and the
+    /// poll that actually retrieved it.
+    pub delay_ms: u64,
+    pub kind: Kind,
+    /// Whether the timestamp of the ID falls in the second of `created_at`, if available.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at_consistent: Option<bool>,
+}
+
+impl<T: TimelineItem> History<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "History capacity must be non-zero");
+        History {
+            generations: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Generation<T>> {
+        self.generations.back()
+    }
+
+    /// Records a new generation, returning the storage of the evicted timeline if the ring was
+    /// full, so that it can be reused for the next request.
+    pub fn push(&mut self, generation: Generation<T>) -> Option<Vec<T>> {
+        let evicted = if self.generations.len() == self.capacity {
+            self.generations.pop_front().map(|g| g.timeline)
+        } else {
+            None
+        };
+        self.generations.push_back(generation);
+        evicted
+    }
+
+    /// Classifies the statuses that are seen for the first time, returning the ones that should
+    /// already have appeared in the recorded polls.
+    pub fn classify<'a>(&self, new: impl IntoIterator<Item = &'a T>, retrieved_ms: u64) -> Vec<Leak>
+    where
+        T: 'a,
+    {
+        let latest_id = if let Some(latest) = self.latest() {
+            latest.latest_id
+        } else {
+            return Vec::new();
+        };
+
+        new.into_iter()
+            // Statuses with higher IDs than the ones observed before cannot have been missed.
+            .filter(|t| t.id() <= latest_id)
+            .filter_map(|t| {
+                let mut missed = self.generations.iter().filter(|g| g.should_contain(t.id()));
+                let first = missed.next()?;
+                Some(Leak {
+                    id: t.id(),
+                    missed_polls: 1 + missed.count(),
+                    first_missed_nth: first.nth,
+                    delay_ms: retrieved_ms.saturating_sub(first.retrieved_ms),
+                    kind: t.kind(),
+                    created_at_consistent: t.is_created_at_consistent(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T: TimelineItem> Generation<T> {
+    /// Returns `true` if the status with the given ID, if it had been visible, would have been
+    /// returned by the request of this generation.
+    fn should_contain(&self, id: u64) -> bool {
+        if id > self.latest_id || self.since_id.is_some_and(|since_id| id <= since_id) {
+            return false;
+        }
+        match self.truncated {
+            Some(Truncated::Oldest) => self.timeline.last().is_none_or(|last| id > last.id()),
+            Some(Truncated::Newest) => self.timeline.first().is_none_or(|first| id <= first.id()),
+            None => true,
+        }
+    }
+}
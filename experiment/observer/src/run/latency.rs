@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use serde::Serialize;
+
+use crate::api::{Kind, TimelineItem};
+
+/// Number of polls between the reports of the rolling histogram.
+const REPORT_INTERVAL: u64 = 60;
+
+/// Measures how long after its ID timestamp each status first becomes visible in the timeline.
+pub struct Latency {
+    /// Latencies of the most recently seen statuses, in the order of their appearance.
+    window: VecDeque<i64>,
+    window_len: usize,
+    output: Option<BufWriter<File>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Status {
+        id: u64,
+        nth: u64,
+        request: &'a serde_json::Value,
+        id_ms: u64,
+        first_seen_ms: u64,
+        response_ms: u64,
+        latency_ms: i64,
+        kind: Kind,
+    },
+    Histogram {
+        nth: u64,
+        #[serde(flatten)]
+        summary: Summary,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub p50_ms: i64,
+    pub p99_ms: i64,
+    pub max_ms: i64,
+}
+
+impl Latency {
+    pub fn new(window_len: usize, output: Option<File>) -> Self {
+        Latency {
+            window: VecDeque::with_capacity(window_len),
+            window_len,
+            output: output.map(BufWriter::new),
+        }
+    }
+
+    /// Records the latencies of the statuses that are seen for the first time in the request
+    /// with the parameters `request`, initiated at `retrieved_ms` and completed at `received_ms`.
+    ///
+    /// The time a status became visible is estimated at the midpoint of the request, since the
+    /// server could have built the response at any point in between.
+    pub fn record<'a, T: TimelineItem + 'a>(
+        &mut self,
+        nth: u64,
+        request: &serde_json::Value,
+        statuses: impl IntoIterator<Item = &'a T>,
+        retrieved_ms: u64,
+        received_ms: u64,
+    ) -> anyhow::Result<()> {
+        let response_ms = received_ms.saturating_sub(retrieved_ms);
+        let first_seen_ms = retrieved_ms + response_ms / 2;
+
+        for t in statuses {
+            let id_ms = T::LAYOUT.timestamp_ms(t.id());
+            let latency_ms = first_seen_ms as i64 - id_ms as i64;
+            tracing::debug!(id = %t.id(), latency_ms, "Observed a new status");
+
+            if self.window.len() == self.window_len {
+                self.window.pop_front();
+            }
+            self.window.push_back(latency_ms);
+
+            self.write(&Record::Status {
+                id: t.id(),
+                nth,
+                request,
+                id_ms,
+                first_seen_ms,
+                response_ms,
+                latency_ms,
+                kind: t.kind(),
+            })?;
+        }
+
+        if nth.is_multiple_of(REPORT_INTERVAL) {
+            if let Some(summary) = self.summary() {
+                tracing::info!(?summary, "Visibility latency of the recent statuses");
+                self.write(&Record::Histogram { nth, summary })?;
+            }
+        }
+
+        if let Some(ref mut output) = self.output {
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the percentiles of the latencies in the current window.
+    pub fn summary(&self) -> Option<Summary> {
+        if self.window.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<i64> = self.window.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+
+        Some(Summary {
+            count: sorted.len(),
+            p50_ms: percentile(50),
+            p99_ms: percentile(99),
+            max_ms: *sorted.last().unwrap(),
+        })
+    }
+
+    fn write(&mut self, record: &Record<'_>) -> anyhow::Result<()> {
+        if let Some(ref mut output) = self.output {
+            serde_json::to_writer(&mut *output, record)?;
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+
+use leaky_snowflake_observer::snowflake::Layout;
+use serde::Serialize;
+
+use crate::api::{self, TimelineItem, TimelineRequest};
+use crate::transport::Transport;
+use crate::util;
+
+use super::INTERVAL;
+
+/// Delays of the probe rounds from the observation of the leak.
+const SCHEDULE: [Duration; 3] = [
+    Duration::from_secs(0),
+    Duration::from_secs(60),
+    Duration::from_secs(300),
+];
+
+/// Results of the repeated bisections of the `since_id` values for which the server returns
+/// a leaked status.
+#[derive(Debug, Serialize)]
+pub struct Probe {
+    pub leaked_id: u64,
+    pub since_id: u64,
+    pub latest_id: u64,
+    pub rounds: Vec<Round>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Round {
+    pub started_ms: u64,
+    pub steps: Vec<Step>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub since_id: u64,
+    pub contained: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Outcome {
+    /// The lowest `since_id` value in the range for which the leaked status was not returned.
+    Boundary { since_id: u64, filter: Filter },
+    /// The leaked status was not returned even with the lowest `since_id` value.
+    Missing,
+    /// The leaked status was returned even with the highest `since_id` value.
+    AboveRange,
+    /// A request has failed during the round.
+    Failed,
+}
+
+/// What the server appears to compare against `since_id`, judging from the boundary.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// The boundary is at the ID itself.
+    RawId,
+    /// The boundary is at the first ID of a millisecond.
+    Timestamp {
+        offset_ms: i64,
+    },
+    Other {
+        offset: i64,
+    },
+}
+
+/// Bisects the `since_id` values between `since_id` and `latest_id` to find the boundary
+/// at which the server stops returning the leaked status, repeatedly over time.
+#[tracing::instrument(skip(request, token, transport))]
+pub async fn bisect<R>(
+    request: &mut R,
+    leaked_id: u64,
+    since_id: u64,
+    latest_id: u64,
+    token: &api::Token,
+    transport: &mut dyn Transport,
+) -> Probe
+where
+    R: Debug + TimelineRequest,
+{
+    let start = tokio::time::Instant::now();
+    let mut rounds = Vec::with_capacity(SCHEDULE.len());
+    for delay in SCHEDULE {
+        tokio::time::sleep_until(start + delay).await;
+        tracing::info!(?delay, "Starting a probe round");
+        let round = bisect_once(request, leaked_id, since_id, latest_id, token, transport).await;
+        tracing::info!(outcome = ?round.outcome, "Finished a probe round");
+        rounds.push(round);
+    }
+
+    Probe {
+        leaked_id,
+        since_id,
+        latest_id,
+        rounds,
+    }
+}
+
+async fn bisect_once<R>(
+    request: &mut R,
+    leaked_id: u64,
+    since_id: u64,
+    latest_id: u64,
+    token: &api::Token,
+    transport: &mut dyn Transport,
+) -> Round
+where
+    R: Debug + TimelineRequest,
+{
+    let started_ms = util::time_to_unix_ms(SystemTime::now());
+    let mut steps = Vec::new();
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    macro_rules! step {
+        ($since_id:expr) => {{
+            let since_id = $since_id;
+            let result = contains(
+                request,
+                since_id,
+                leaked_id,
+                token,
+                transport,
+                &mut interval,
+            )
+            .await;
+            match result {
+                Some(contained) => {
+                    steps.push(Step {
+                        since_id,
+                        contained,
+                    });
+                    contained
+                }
+                None => {
+                    return Round {
+                        started_ms,
+                        steps,
+                        outcome: Outcome::Failed,
+                    };
+                }
+            }
+        }};
+    }
+
+    let outcome = if !step!(since_id) {
+        Outcome::Missing
+    } else if step!(latest_id) {
+        Outcome::AboveRange
+    } else {
+        // Invariant: the status is returned with `lo` but not with `hi`.
+        let (mut lo, mut hi) = (since_id, latest_id);
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if step!(mid) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Outcome::Boundary {
+            since_id: hi,
+            filter: Filter::new(&R::Item::LAYOUT, hi, leaked_id),
+        }
+    };
+
+    Round {
+        started_ms,
+        steps,
+        outcome,
+    }
+}
+
+/// Returns whether the response with the given `since_id` contains the leaked status,
+/// or `None` if the request has failed.
+async fn contains<R>(
+    request: &mut R,
+    since_id: u64,
+    leaked_id: u64,
+    token: &api::Token,
+    transport: &mut dyn Transport,
+    interval: &mut tokio::time::Interval,
+) -> Option<bool>
+where
+    R: Debug + TimelineRequest,
+{
+    // Pace the requests so that the probe does not exhaust the rate limit at once.
+    interval.tick().await;
+    request.set_since_id(Some(since_id));
+    let result = request
+        .fetch(PhantomData::<Vec<R::Item>>, token, transport)
+        .await;
+    match result {
+        Ok(timeline) => Some(timeline.iter().any(|t| t.id() == leaked_id)),
+        Err(cause) => {
+            tracing::error!(?cause, "Error in API request");
+            None
+        }
+    }
+}
+
+impl Filter {
+    fn new(layout: &Layout, boundary: u64, leaked_id: u64) -> Self {
+        if boundary == leaked_id {
+            Filter::RawId
+        } else if layout.min_id_at(layout.timestamp_ms(boundary)) == Some(boundary) {
+            let offset_ms =
+                layout.timestamp_ms(boundary) as i64 - layout.timestamp_ms(leaked_id) as i64;
+            Filter::Timestamp { offset_ms }
+        } else {
+            Filter::Other {
+                offset: boundary as i64 - leaked_id as i64,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_filter(layout: &Layout, boundary: u64, leaked_id: u64, expected: &str) {
+        let filter = serde_json::to_string(&Filter::new(layout, boundary, leaked_id)).unwrap();
+        assert_eq!(filter, expected);
+    }
+
+    #[test]
+    fn filter_twitter() {
+        let layout = &Layout::TWITTER;
+        let leaked_id = 1050118621198921728 | 3 << 12 | 5;
+        assert_filter(layout, leaked_id, leaked_id, r#""raw_id""#);
+        let boundary = layout
+            .min_id_at(layout.timestamp_ms(leaked_id) - 1000)
+            .unwrap();
+        assert_filter(
+            layout,
+            boundary,
+            leaked_id,
+            r#"{"timestamp":{"offset_ms":-1000}}"#,
+        );
+        assert_filter(
+            layout,
+            leaked_id - 2,
+            leaked_id,
+            r#"{"other":{"offset":-2}}"#,
+        );
+    }
+
+    #[test]
+    fn filter_mastodon() {
+        let layout = &Layout::MASTODON;
+        let leaked_id = 109317433532291627;
+        let boundary = layout
+            .min_id_at(layout.timestamp_ms(leaked_id) - 1000)
+            .unwrap();
+        assert_filter(
+            layout,
+            boundary,
+            leaked_id,
+            r#"{"timestamp":{"offset_ms":-1000}}"#,
+        );
+        // The first ID of a millisecond is not aligned to Twitter's 22-bit timestamp shift.
+        let boundary = layout
+            .min_id_at(layout.timestamp_ms(leaked_id) + 1)
+            .unwrap();
+        assert_ne!(boundary & ((1 << 22) - 1), 0);
+        assert_filter(
+            layout,
+            boundary,
+            leaked_id,
+            r#"{"timestamp":{"offset_ms":1}}"#,
+        );
+        assert_filter(
+            layout,
+            boundary + 1,
+            leaked_id,
+            &format!(r#"{{"other":{{"offset":{}}}}}"#, boundary + 1 - leaked_id),
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::api::stream::{self, Endpoint, StreamTweet};
+use crate::api::{self, TimelineItem};
+use crate::transport::Transport;
+use crate::util;
+
+use super::StreamArgs;
+
+/// Number of polls between the reports of the summary.
+const REPORT_INTERVAL: u64 = 60;
+
+/// Compares the polled timeline against the statuses delivered by the filtered stream, which
+/// is treated as the ground truth.
+pub struct GroundTruth {
+    receiver: mpsc::UnboundedReceiver<(StreamTweet, u64)>,
+    /// Statuses delivered by the stream but not yet seen in the timeline, with the time they
+    /// were delivered.
+    pending: HashMap<u64, (StreamTweet, u64)>,
+    /// Statuses seen in the timeline within the budget, with the time they were last seen, so
+    /// that the statuses delivered by the stream after the timeline are not reported.
+    seen: HashMap<u64, u64>,
+    budget_ms: u64,
+    streamed: u64,
+    misses: u64,
+    output: BufWriter<File>,
+}
+
+#[derive(Serialize)]
+struct Miss<'a> {
+    nth: u64,
+    request: &'a serde_json::Value,
+    id: u64,
+    author_id: u64,
+    id_ms: u64,
+    streamed_ms: u64,
+}
+
+impl GroundTruth {
+    /// Sets up the stream rules for the members of the List and starts consuming the stream
+    /// in the background.
+    pub async fn start(
+        StreamArgs {
+            members,
+            token,
+            budget_ms,
+            output,
+        }: StreamArgs,
+        user_token: &api::Token,
+        transport: &mut dyn Transport,
+    ) -> anyhow::Result<Self> {
+        let users = api::fetch_pages(&members, user_token, transport).await?;
+        let user_ids: Vec<u64> = users.iter().map(|u| u.id).collect();
+        tracing::info!(members = user_ids.len(), "Retrieved the List members");
+
+        let mut stream_sender = util::http2_connect(api::HOST, util::HTTPS_DEFAULT_PORT).await?;
+        stream::set_rules(&user_ids, &token, &mut stream_sender).await?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(stream::forward(
+            Endpoint::Filtered,
+            token,
+            Some(stream_sender),
+            sender,
+        ));
+
+        Ok(GroundTruth {
+            receiver,
+            pending: HashMap::new(),
+            seen: HashMap::new(),
+            budget_ms,
+            streamed: 0,
+            misses: 0,
+            output: BufWriter::new(output),
+        })
+    }
+
+    /// Matches the timeline retrieved in the request initiated at `retrieved_ms` against the
+    /// streamed statuses, and reports the ones that have not appeared within the budget.
+    pub fn record<T: TimelineItem>(
+        &mut self,
+        nth: u64,
+        request: &serde_json::Value,
+        timeline: &[T],
+        retrieved_ms: u64,
+    ) -> anyhow::Result<()> {
+        for t in timeline {
+            self.pending.remove(&t.id());
+            self.seen.insert(t.id(), retrieved_ms);
+        }
+        self.seen
+            .retain(|_, &mut seen_ms| seen_ms + self.budget_ms >= retrieved_ms);
+
+        while let Ok((tweet, streamed_ms)) = self.receiver.try_recv() {
+            self.streamed += 1;
+            if !self.seen.contains_key(&tweet.id) {
+                self.pending.insert(tweet.id, (tweet, streamed_ms));
+            }
+        }
+
+        let budget_ms = self.budget_ms;
+        let mut misses: Vec<_> = self
+            .pending
+            .extract_if(|_, &mut (_, streamed_ms)| streamed_ms + budget_ms < retrieved_ms)
+            .map(|(_, miss)| miss)
+            .collect();
+        misses.sort_unstable_by_key(|(tweet, _)| tweet.id);
+        for (tweet, streamed_ms) in misses {
+            let id_ms = util::sf_to_unix_ms(tweet.id);
+            tracing::info!(id = %tweet.id, id_ms, streamed_ms, "Missed a streamed status");
+            self.misses += 1;
+            serde_json::to_writer(
+                &mut self.output,
+                &Miss {
+                    nth,
+                    request,
+                    id: tweet.id,
+                    author_id: tweet.author_id,
+                    id_ms,
+                    streamed_ms,
+                },
+            )?;
+            writeln!(self.output)?;
+        }
+        self.output.flush()?;
+
+        if nth.is_multiple_of(REPORT_INTERVAL) {
+            tracing::info!(
+                streamed = self.streamed,
+                pending = self.pending.len(),
+                misses = self.misses,
+                "Comparison against the stream"
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::api::{self, Request, TimelineItem};
+use crate::transport::{self, Transport};
+use crate::util;
+
+use super::history::{Generation, Truncated};
+
+/// Maximum number of IDs accepted by the `lookup` endpoints in a single request.
+const MAX_LOOKUP_LEN: usize = 100;
+
+/// A status that was present in the previous timeline but is missing from the latest one.
+#[derive(Debug, Serialize)]
+pub struct Vanished {
+    pub id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<u64>,
+    pub reason: Reason,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The status is still available, so it was only omitted from the timeline.
+    Visible,
+    Deleted,
+    /// The author has protected their statuses.
+    Protected,
+    /// The author's account is suspended (or deactivated).
+    UserSuspended,
+    /// The probe has failed.
+    Unknown,
+}
+
+/// Returns the statuses of the `previous` timeline that should be contained in `timeline`
+/// (sorted in reverse ID order) retrieved with the given `since_id` but are missing from it.
+pub fn diff<'a, T: TimelineItem>(
+    previous: &'a Generation<T>,
+    timeline: &[T],
+    since_id: Option<u64>,
+    truncated: Option<Truncated>,
+) -> Vec<&'a T> {
+    let lower = match (truncated, timeline.last()) {
+        (Some(Truncated::Oldest), Some(last)) => Some(last.id() - 1),
+        _ => since_id,
+    };
+    let upper = match (truncated, timeline.first()) {
+        (Some(Truncated::Newest), Some(first)) => first.id(),
+        _ => u64::MAX,
+    };
+    previous
+        .timeline
+        .iter()
+        .skip_while(|t| t.id() > upper)
+        .take_while(|t| lower.is_none_or(|lower| t.id() > lower))
+        .filter(|t| timeline.binary_search_by(|u| t.id().cmp(&u.id())).is_err())
+        .collect()
+}
+
+/// Looks the vanished statuses up and records them in the background, so that the lookups do
+/// not delay the polling of the timeline.
+pub struct Prober {
+    sender: mpsc::UnboundedSender<Batch>,
+    task: JoinHandle<()>,
+}
+
+/// The statuses found to have vanished in a poll.
+struct Batch {
+    nth: u64,
+    retrieved_ms: u64,
+    request: serde_json::Value,
+    statuses: Vec<Vanished>,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    nth: u64,
+    retrieved_ms: u64,
+    request: &'a serde_json::Value,
+    #[serde(flatten)]
+    status: &'a Vanished,
+}
+
+impl Prober {
+    /// Starts the lookups of the vanished statuses of a timeline on `host`, connecting to the
+    /// Twitter API over HTTP/1.1 if `http1` is `true`.
+    pub fn start(host: &str, http1: bool, token: api::Token, output: Option<File>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let lookup = host == api::HOST;
+        let task = tokio::spawn(async move {
+            if let Err(cause) = run(receiver, lookup, http1, token, output).await {
+                tracing::error!(?cause, "Error while recording the vanished statuses");
+            }
+        });
+        Prober { sender, task }
+    }
+
+    /// Waits for the queued statuses to be looked up and recorded.
+    pub async fn finish(self) {
+        drop(self.sender);
+        if let Err(cause) = self.task.await {
+            tracing::error!(?cause, "The lookups of the vanished statuses have panicked");
+        }
+    }
+
+    /// Queues the statuses found to have vanished in the poll initiated at `retrieved_ms`
+    /// with the parameters `request`.
+    pub fn probe<T: TimelineItem>(
+        &self,
+        nth: u64,
+        retrieved_ms: u64,
+        request: &serde_json::Value,
+        statuses: &[&T],
+    ) {
+        let statuses = statuses
+            .iter()
+            .map(|t| Vanished {
+                id: t.id(),
+                user_id: t.author_id(),
+                reason: Reason::Unknown,
+            })
+            .collect();
+        let batch = Batch {
+            nth,
+            retrieved_ms,
+            request: request.clone(),
+            statuses,
+        };
+        if self.sender.send(batch).is_err() {
+            tracing::warn!("The lookups of the vanished statuses have stopped");
+        }
+    }
+}
+
+async fn run(
+    mut receiver: mpsc::UnboundedReceiver<Batch>,
+    lookup: bool,
+    http1: bool,
+    token: api::Token,
+    output: Option<File>,
+) -> anyhow::Result<()> {
+    let mut output = output.map(BufWriter::new);
+    let mut transport = None;
+    while let Some(mut batch) = receiver.recv().await {
+        // The lookups are made against the Twitter API, so the statuses of a timeline on any
+        // other host are left `Unknown`.
+        if lookup {
+            let transport = match transport {
+                Some(ref mut transport) => transport,
+                None => transport
+                    .insert(transport::connect(api::HOST, util::HTTPS_DEFAULT_PORT, http1).await?),
+            };
+            probe(&mut batch.statuses, &token, &mut **transport).await;
+        }
+
+        for status in &batch.statuses {
+            tracing::info!(id = %status.id, reason = ?status.reason, "Observed a vanished status");
+            if let Some(ref mut output) = output {
+                let record = Record {
+                    nth: batch.nth,
+                    retrieved_ms: batch.retrieved_ms,
+                    request: &batch.request,
+                    status,
+                };
+                serde_json::to_writer(&mut *output, &record)?;
+                writeln!(output)?;
+            }
+        }
+        if let Some(ref mut output) = output {
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Classifies the vanished statuses by looking them and their authors up.
+async fn probe(statuses: &mut [Vanished], token: &api::Token, transport: &mut dyn Transport) {
+    for chunk in statuses.chunks_mut(MAX_LOOKUP_LEN) {
+        match probe_chunk(chunk, token, transport).await {
+            Ok(()) => {}
+            Err(cause) => {
+                tracing::error!(?cause, "Error while probing the vanished statuses");
+                if cause.is::<hyper::Error>() {
+                    if let Err(cause) = transport.reconnect().await {
+                        tracing::error!(?cause, "Unable to reconnect");
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn probe_chunk(
+    statuses: &mut [Vanished],
+    token: &api::Token,
+    transport: &mut dyn Transport,
+) -> anyhow::Result<()> {
+    #[derive(Deserialize)]
+    struct StatusMap {
+        id: HashMap<u64, Option<serde::de::IgnoredAny>>,
+    }
+
+    #[derive(Deserialize)]
+    struct User {
+        id: u64,
+        protected: bool,
+    }
+
+    let ids = statuses.iter().map(|t| t.id).collect();
+    let StatusMap { id: found } = api::statuses::Lookup::new(ids)
+        .fetch(PhantomData, token, transport)
+        .await?;
+
+    let mut unavailable = Vec::new();
+    for t in &mut *statuses {
+        if let Some(Some(_)) = found.get(&t.id) {
+            t.reason = Reason::Visible;
+        } else {
+            unavailable.push(t);
+        }
+    }
+
+    if unavailable.is_empty() {
+        return Ok(());
+    }
+
+    let user_ids: HashSet<u64> = unavailable.iter().filter_map(|t| t.user_id).collect();
+    let result = api::users::Lookup::new(user_ids.into_iter().collect())
+        .fetch(PhantomData::<Vec<User>>, token, transport)
+        .await;
+    let users: HashMap<u64, bool> = match result {
+        Ok(users) => users.into_iter().map(|u| (u.id, u.protected)).collect(),
+        // The endpoint responds with 404 if none of the users is available.
+        Err(e)
+            if matches!(
+                e.downcast_ref::<api::StatusError>(),
+                Some(api::StatusError(hyper::StatusCode::NOT_FOUND))
+            ) =>
+        {
+            HashMap::new()
+        }
+        Err(e) => return Err(e),
+    };
+
+    for t in unavailable {
+        t.reason = match t.user_id.map(|id| users.get(&id)) {
+            Some(Some(true)) => Reason::Protected,
+            Some(Some(false)) => Reason::Deleted,
+            Some(None) => Reason::UserSuspended,
+            None => Reason::Unknown,
+        };
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use leaky_snowflake_observer::snowflake::Layout;
+use serde::Serialize;
+
+use crate::api::TimelineItem;
+
+use super::history::Leak;
+
+/// Number of polls between the reports of the table.
+const REPORT_INTERVAL: u64 = 60;
+/// Number of the most recent latencies kept for each worker.
+const WINDOW_LEN: usize = 100;
+/// Minimum number of latencies of a worker needed to estimate its clock offset.
+const MIN_SAMPLES: usize = 5;
+/// Number of the workers logged in each report.
+const LOG_ROWS: usize = 5;
+
+/// Attributes the ordering violations and leaks to the Snowflake workers that generated the IDs.
+pub struct Workers {
+    layout: Layout,
+    workers: BTreeMap<(u64, u64), Stats>,
+    output: Option<BufWriter<File>>,
+}
+
+#[derive(Default)]
+struct Stats {
+    statuses: u64,
+    violations: u64,
+    leaks: u64,
+    /// Latencies of the most recently seen statuses of the worker.
+    window: VecDeque<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Table {
+    nth: u64,
+    rows: Vec<Row>,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    datacenter_id: u64,
+    worker_id: u64,
+    statuses: u64,
+    /// Number of the statuses that appeared below the highest ID of the earlier polls.
+    violations: u64,
+    leaks: u64,
+    /// Clock offset of the worker relative to the median of all the workers, estimated from
+    /// the visibility latencies. A positive value means the clock of the worker is ahead.
+    offset_ms: Option<i64>,
+}
+
+impl Workers {
+    pub fn new(layout: Layout, output: Option<File>) -> Self {
+        Workers {
+            layout,
+            workers: BTreeMap::new(),
+            output: output.map(BufWriter::new),
+        }
+    }
+
+    /// Records the statuses that are seen for the first time in the request initiated at
+    /// `retrieved_ms` and completed at `received_ms`, where `latest_id` is the highest ID
+    /// observed in the earlier polls.
+    pub fn record<'a, T: TimelineItem + 'a>(
+        &mut self,
+        nth: u64,
+        statuses: impl IntoIterator<Item = &'a T>,
+        leaks: &[Leak],
+        latest_id: Option<u64>,
+        retrieved_ms: u64,
+        received_ms: u64,
+    ) -> anyhow::Result<()> {
+        let layout = self.layout;
+        // See `Latency::record` for the choice of the midpoint.
+        let first_seen_ms = retrieved_ms + received_ms.saturating_sub(retrieved_ms) / 2;
+
+        for t in statuses {
+            let stats = self.stats(t.id());
+            stats.statuses += 1;
+            if latest_id.is_some_and(|latest_id| t.id() < latest_id) {
+                stats.violations += 1;
+            }
+            if stats.window.len() == WINDOW_LEN {
+                stats.window.pop_front();
+            }
+            stats
+                .window
+                .push_back(first_seen_ms as i64 - layout.timestamp_ms(t.id()) as i64);
+        }
+        for leak in leaks {
+            self.stats(leak.id).leaks += 1;
+        }
+
+        if nth.is_multiple_of(REPORT_INTERVAL) {
+            self.report(nth)?;
+        }
+
+        Ok(())
+    }
+
+    fn stats(&mut self, id: u64) -> &mut Stats {
+        let layout = self.layout;
+        self.workers
+            .entry((layout.datacenter_id(id), layout.worker_id(id)))
+            .or_default()
+    }
+
+    fn report(&mut self, nth: u64) -> anyhow::Result<()> {
+        let mut all: Vec<i64> = self
+            .workers
+            .values()
+            .flat_map(|stats| stats.window.iter().copied())
+            .collect();
+        let Some(all_median) = median(&mut all) else {
+            return Ok(());
+        };
+
+        let mut rows: Vec<Row> = self
+            .workers
+            .iter()
+            .map(|(&(datacenter_id, worker_id), stats)| {
+                let offset_ms = if stats.window.len() >= MIN_SAMPLES {
+                    let mut window: Vec<i64> = stats.window.iter().copied().collect();
+                    // A worker whose clock is ahead stamps later times, so its statuses appear
+                    // to have become visible sooner.
+                    median(&mut window).map(|m| all_median - m)
+                } else {
+                    None
+                };
+                Row {
+                    datacenter_id,
+                    worker_id,
+                    statuses: stats.statuses,
+                    violations: stats.violations,
+                    leaks: stats.leaks,
+                    offset_ms,
+                }
+            })
+            .collect();
+        rows.sort_by_key(|row| Reverse((row.leaks, row.violations)));
+
+        for row in rows.iter().take(LOG_ROWS) {
+            tracing::info!(
+                row.datacenter_id,
+                row.worker_id,
+                row.statuses,
+                row.violations,
+                row.leaks,
+                row.offset_ms,
+                "Ordering violations by worker"
+            );
+        }
+
+        if let Some(ref mut output) = self.output {
+            serde_json::to_writer(&mut *output, &Table { nth, rows })?;
+            writeln!(output)?;
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn median(values: &mut [i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mid = values.len() / 2;
+    Some(*values.select_nth_unstable(mid).1)
+}
@@ -0,0 +1,508 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashSet};
+use std::ffi::OsString;
+use std::io::{stdout, Write};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use getopts::{Matches, Options};
+
+use leaky_snowflake_observer::poller::SinceIdPolicy;
+use leaky_snowflake_observer::snowflake::{self, IdWorker, Layout};
+
+/// Unix time at which the simulated clock starts, in milliseconds.
+const START_UNIX_MS: u64 = 1_672_531_200_000;
+/// Period at the beginning of the simulation in which statuses are not evaluated,
+/// so that the statuses predating the first poll do not count as lost.
+const WARM_UP_US: u64 = 10_000_000;
+const MAX_TIMELINE_LEN: usize = 200;
+
+/// Parameters of a simulated system.
+#[derive(Clone, Copy, Debug)]
+struct Config {
+    workers: u64,
+    /// Maximum absolute clock offset of a worker from the true time.
+    offset_ms: f64,
+    /// Maximum absolute clock drift rate of a worker.
+    drift_ppm: f64,
+    /// Interval of the NTP corrections, which resample the offsets of the workers.
+    ntp_interval_ms: u64,
+    /// Posting rate of each worker in statuses per second.
+    rate: f64,
+    /// Mean delay between the creation of a status and its appearance in the timeline.
+    fanout_ms: f64,
+    /// Clock offset of the pollers from the true time.
+    poller_offset_ms: f64,
+    interval_ms: u64,
+    duration_ms: u64,
+}
+
+/// Outcome of a poller in a simulation run.
+struct Outcome {
+    policy: SinceIdPolicy,
+    /// Number of the statuses that were visible during the evaluated period.
+    statuses: u64,
+    lost: u64,
+    duplicates: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Post { worker: usize },
+    Visible { id: u64 },
+    Poll { poller: usize },
+    Sync { worker: usize },
+}
+
+struct Worker {
+    id_worker: IdWorker,
+    offset_ms: f64,
+    drift: f64,
+    synced_at_us: u64,
+}
+
+struct Poller {
+    policy: SinceIdPolicy,
+    latest: Option<(u64, u64)>,
+    delivered: HashSet<u64>,
+    duplicates: u64,
+    last_poll_us: u64,
+}
+
+/// A SplitMix64 pseudorandom number generator.
+struct Rng(u64);
+
+pub fn main(args: Vec<OsString>) -> anyhow::Result<ExitCode> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap();
+
+    let mut opts = Options::new();
+    opts.optopt("", "workers", "number of ID workers (default: 8)", "N,..");
+    opts.optopt(
+        "",
+        "offset",
+        "maximum clock offset of the workers (default: 10)",
+        "MILLIS,..",
+    );
+    opts.optopt(
+        "",
+        "drift",
+        "maximum clock drift of the workers (default: 50)",
+        "PPM,..",
+    );
+    opts.optopt(
+        "",
+        "ntp-interval",
+        "interval of the NTP corrections of the workers (default: 64000)",
+        "MILLIS,..",
+    );
+    opts.optopt(
+        "",
+        "rate",
+        "statuses posted per second per worker (default: 1)",
+        "RATE,..",
+    );
+    opts.optopt(
+        "",
+        "fanout",
+        "mean delay before a status appears in the timeline (default: 50)",
+        "MILLIS,..",
+    );
+    opts.optopt(
+        "",
+        "poller-offset",
+        "clock offset of the pollers (default: 0)",
+        "MILLIS,..",
+    );
+    opts.optopt(
+        "",
+        "interval",
+        "polling interval (default: 1000)",
+        "MILLIS,..",
+    );
+    opts.optopt(
+        "",
+        "duration",
+        "simulated duration (default: 3600000)",
+        "MILLIS",
+    );
+    opts.optopt(
+        "k",
+        "",
+        "`k` values of the adjusted pollers (default: 1000)",
+        "MILLIS,..",
+    );
+    opts.optopt("", "seed", "seed of the random number generator", "N");
+    opts.optflag("h", "help", "print this help");
+
+    let matches = opts.parse(args)?;
+
+    let program = program.to_string_lossy();
+    if matches.opt_present("h") {
+        print_usage(&program, &opts);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let workers = get_list(&matches, "workers", 8)?;
+    let offsets = get_list(&matches, "offset", 10.)?;
+    let drifts = get_list(&matches, "drift", 50.)?;
+    let ntp_intervals = get_list(&matches, "ntp-interval", 64_000)?;
+    let rates = get_list(&matches, "rate", 1.)?;
+    let fanouts = get_list(&matches, "fanout", 50.)?;
+    let poller_offsets = get_list(&matches, "poller-offset", 0.)?;
+    let intervals = get_list(&matches, "interval", 1000)?;
+    let duration_ms = matches.opt_get_default("duration", 3_600_000)?;
+    let policies: Vec<_> = std::iter::once(SinceIdPolicy::Naive)
+        .chain(
+            get_list(&matches, "k", 1000)?
+                .into_iter()
+                .map(|k_ms| SinceIdPolicy::Adjusted { k_ms }),
+        )
+        .collect();
+    let seed = matches.opt_get_default("seed", 0)?;
+
+    let max_workers =
+        (Layout::TWITTER.max_datacenter_id() + 1) * (Layout::TWITTER.max_worker_id() + 1);
+    if workers.iter().any(|&n| n > max_workers) {
+        anyhow::bail!("`--workers` must not exceed {}", max_workers);
+    }
+    if workers.contains(&0) || ntp_intervals.contains(&0) || intervals.contains(&0) {
+        anyhow::bail!("`--workers`, `--ntp-interval` and `--interval` must be positive integers");
+    }
+    if rates.iter().any(|&rate| rate <= 0.) {
+        anyhow::bail!("`--rate` must be positive");
+    }
+
+    let mut stdout = stdout().lock();
+    writeln!(
+        stdout,
+        "workers,offset_ms,drift_ppm,ntp_interval_ms,rate,fanout_ms,poller_offset_ms,interval_ms,\
+        policy,k_ms,statuses,lost,duplicates,leak_rate,duplicate_rate"
+    )?;
+
+    let mut configs = vec![Config {
+        workers: workers[0],
+        offset_ms: offsets[0],
+        drift_ppm: drifts[0],
+        ntp_interval_ms: ntp_intervals[0],
+        rate: rates[0],
+        fanout_ms: fanouts[0],
+        poller_offset_ms: poller_offsets[0],
+        interval_ms: intervals[0],
+        duration_ms,
+    }];
+    configs = sweep(configs, &workers, |c, v| c.workers = v);
+    configs = sweep(configs, &offsets, |c, v| c.offset_ms = v);
+    configs = sweep(configs, &drifts, |c, v| c.drift_ppm = v);
+    configs = sweep(configs, &ntp_intervals, |c, v| c.ntp_interval_ms = v);
+    configs = sweep(configs, &rates, |c, v| c.rate = v);
+    configs = sweep(configs, &fanouts, |c, v| c.fanout_ms = v);
+    configs = sweep(configs, &poller_offsets, |c, v| c.poller_offset_ms = v);
+    configs = sweep(configs, &intervals, |c, v| c.interval_ms = v);
+
+    for config in &configs {
+        tracing::debug!(?config, "Simulating");
+        for outcome in simulate(config, &policies, seed) {
+            write_row(&mut stdout, config, &outcome)?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} simulate [OPTIONS..]\n\n\
+        Options taking comma-separated values are swept over all of their combinations.",
+        program
+    );
+    print!("{}", opts.usage(&brief));
+}
+
+fn get_list<T: FromStr>(matches: &Matches, name: &str, default: T) -> anyhow::Result<Vec<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match matches.opt_str(name) {
+        Some(values) => values
+            .split(',')
+            .map(|v| v.trim().parse().map_err(Into::into))
+            .collect(),
+        None => Ok(vec![default]),
+    }
+}
+
+/// Returns the configurations with every value of a parameter for each of `configs`.
+fn sweep<T: Copy>(configs: Vec<Config>, values: &[T], set: fn(&mut Config, T)) -> Vec<Config> {
+    configs
+        .into_iter()
+        .flat_map(|config| {
+            values.iter().map(move |&v| {
+                let mut config = config;
+                set(&mut config, v);
+                config
+            })
+        })
+        .collect()
+}
+
+fn write_row(w: &mut impl Write, config: &Config, outcome: &Outcome) -> anyhow::Result<()> {
+    let (policy, k_ms) = match outcome.policy {
+        SinceIdPolicy::Naive => ("naive", String::new()),
+        SinceIdPolicy::Adjusted { k_ms } => ("adjusted", k_ms.to_string()),
+    };
+    let ratio = |n: u64| {
+        if outcome.statuses == 0 {
+            0.
+        } else {
+            n as f64 / outcome.statuses as f64
+        }
+    };
+    writeln!(
+        w,
+        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        config.workers,
+        config.offset_ms,
+        config.drift_ppm,
+        config.ntp_interval_ms,
+        config.rate,
+        config.fanout_ms,
+        config.poller_offset_ms,
+        config.interval_ms,
+        policy,
+        k_ms,
+        outcome.statuses,
+        outcome.lost,
+        outcome.duplicates,
+        ratio(outcome.lost),
+        ratio(outcome.duplicates),
+    )?;
+    Ok(())
+}
+
+/// Runs a simulation with a poller for each of the `policies`.
+fn simulate(config: &Config, policies: &[SinceIdPolicy], seed: u64) -> Vec<Outcome> {
+    let mut rng = Rng(seed);
+    let duration_us = config.duration_ms * 1000;
+    let ntp_interval_us = config.ntp_interval_ms * 1000;
+    let interval_us = config.interval_ms * 1000;
+
+    let max_worker_id = Layout::TWITTER.max_worker_id() + 1;
+    let mut workers: Vec<Worker> = (0..config.workers)
+        .map(|i| Worker {
+            id_worker: IdWorker::new(i / max_worker_id, i % max_worker_id).unwrap(),
+            offset_ms: rng.uniform(config.offset_ms),
+            drift: rng.uniform(config.drift_ppm) * 1e-6,
+            synced_at_us: 0,
+        })
+        .collect();
+    let mut pollers: Vec<Poller> = policies
+        .iter()
+        .map(|&policy| Poller {
+            policy,
+            latest: None,
+            delivered: HashSet::new(),
+            duplicates: 0,
+            last_poll_us: 0,
+        })
+        .collect();
+
+    let mut events = BinaryHeap::new();
+    for worker in 0..workers.len() {
+        events.push(Reverse((
+            rng.exponential_us(config.rate),
+            Event::Post { worker },
+        )));
+        // Desynchronize the NTP corrections of the workers.
+        let phase = rng.below(ntp_interval_us);
+        events.push(Reverse((phase, Event::Sync { worker })));
+    }
+    // All the pollers poll at the same time so that they see the same timeline.
+    let phase = rng.below(interval_us);
+    for poller in 0..pollers.len() {
+        events.push(Reverse((phase, Event::Poll { poller })));
+    }
+
+    // IDs of the visible statuses, and those created after the warm-up period.
+    let mut timeline = BTreeSet::new();
+    let mut evaluated = BTreeSet::new();
+    // Visibility times of the evaluated statuses.
+    let mut visible_at = Vec::new();
+
+    // Keep polling for a while after the posting has stopped to let the pollers catch up.
+    let end_us = duration_us + 10 * interval_us;
+    while let Some(Reverse((now, event))) = events.pop() {
+        if now > end_us {
+            break;
+        }
+        match event {
+            Event::Post { worker } => {
+                if now < duration_us {
+                    let id = match workers[worker].next_id(now) {
+                        Ok(id) => id,
+                        Err(_) => {
+                            // The worker has rejected the request. Retry in a millisecond.
+                            events.push(Reverse((now + 1000, event)));
+                            continue;
+                        }
+                    };
+                    let delay = rng.exponential_us(1000. / config.fanout_ms);
+                    events.push(Reverse((now + delay, Event::Visible { id })));
+                    if now >= WARM_UP_US {
+                        evaluated.insert(id);
+                    }
+                    let next = now + rng.exponential_us(config.rate);
+                    events.push(Reverse((next, Event::Post { worker })));
+                }
+            }
+            Event::Visible { id } => {
+                timeline.insert(id);
+                if evaluated.contains(&id) {
+                    visible_at.push((id, now));
+                }
+            }
+            Event::Poll { poller } => {
+                let unix_ms = (START_UNIX_MS as f64 + now as f64 / 1000. + config.poller_offset_ms)
+                    .max(0.) as u64;
+                pollers[poller].poll(&timeline, now, unix_ms);
+                events.push(Reverse((now + interval_us, Event::Poll { poller })));
+            }
+            Event::Sync { worker } => {
+                let worker = &mut workers[worker];
+                worker.offset_ms = rng.uniform(config.offset_ms);
+                worker.drift = rng.uniform(config.drift_ppm) * 1e-6;
+                worker.synced_at_us = now;
+                events.push(Reverse((now + ntp_interval_us, event)));
+            }
+        }
+    }
+
+    pollers
+        .into_iter()
+        .map(|p| {
+            // Statuses that became visible after the last poll cannot have been delivered.
+            let visible: Vec<u64> = visible_at
+                .iter()
+                .filter(|&&(_, at)| at <= p.last_poll_us)
+                .map(|&(id, _)| id)
+                .collect();
+            let lost = visible
+                .iter()
+                .filter(|id| !p.delivered.contains(id))
+                .count() as u64;
+            Outcome {
+                policy: p.policy,
+                statuses: visible.len() as u64,
+                lost,
+                duplicates: p.duplicates,
+            }
+        })
+        .collect()
+}
+
+impl Worker {
+    /// Generates a Snowflake ID from the worker's clock at the true time `now_us`.
+    fn next_id(&mut self, now_us: u64) -> Result<u64, snowflake::Error> {
+        let elapsed_ms = (now_us - self.synced_at_us) as f64 / 1000.;
+        let local_ms =
+            START_UNIX_MS as f64 + now_us as f64 / 1000. + self.offset_ms + elapsed_ms * self.drift;
+        self.id_worker.next_id_at(local_ms as u64)
+    }
+}
+
+impl Poller {
+    fn poll(&mut self, timeline: &BTreeSet<u64>, now_us: u64, retrieved_ms: u64) {
+        let since_id = self.latest.map(|(latest_id, retrieved_ms)| {
+            self.policy
+                .since_id(&Layout::TWITTER, latest_id, retrieved_ms)
+        });
+        let lower = since_id.map_or(0, |since_id| since_id + 1);
+        let mut latest_id = self.latest.map(|(latest_id, _)| latest_id);
+        for &id in timeline.range(lower..).rev().take(MAX_TIMELINE_LEN) {
+            if !self.delivered.insert(id) {
+                self.duplicates += 1;
+            }
+            latest_id = latest_id.max(Some(id));
+        }
+        if let Some(latest_id) = latest_id {
+            self.latest = Some((latest_id, retrieved_ms));
+        }
+        self.last_poll_us = now_us;
+    }
+}
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a sample from `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a sample from `[-max, max)`.
+    fn uniform(&mut self, max: f64) -> f64 {
+        (self.unit() * 2. - 1.) * max
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// Returns a sample of the interval between events of a Poisson process with the given
+    /// rate per second, in microseconds.
+    fn exponential_us(&mut self, rate: f64) -> u64 {
+        (-(1. - self.unit()).ln() / rate * 1e6) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 42;
+
+    fn config() -> Config {
+        Config {
+            workers: 8,
+            offset_ms: 0.,
+            drift_ppm: 0.,
+            ntp_interval_ms: 64_000,
+            rate: 10.,
+            fanout_ms: 0.,
+            poller_offset_ms: 0.,
+            interval_ms: 1000,
+            duration_ms: 120_000,
+        }
+    }
+
+    #[test]
+    fn synchronized() {
+        let outcome = &simulate(&config(), &[SinceIdPolicy::Naive], SEED)[0];
+        assert!(outcome.statuses > 0);
+        assert_eq!(outcome.lost, 0);
+    }
+
+    #[test]
+    fn adjusted() {
+        let config = Config {
+            offset_ms: 10.,
+            drift_ppm: 50.,
+            fanout_ms: 50.,
+            ..config()
+        };
+        // Well above the offsets, the drift over an NTP interval and the fan-out delays.
+        let k_ms = 1000;
+        let policies = [SinceIdPolicy::Naive, SinceIdPolicy::Adjusted { k_ms }];
+        let outcomes = simulate(&config, &policies, SEED);
+        let (naive, adjusted) = (&outcomes[0], &outcomes[1]);
+        assert!(naive.lost > 0);
+        assert_eq!(adjusted.statuses, naive.statuses);
+        assert_eq!(adjusted.lost, 0);
+    }
+}
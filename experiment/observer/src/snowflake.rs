@@ -0,0 +1,386 @@
+use std::fmt;
+use std::time::SystemTime;
+
+/// The custom epoch of Twitter's Snowflake IDs, in Unix milliseconds.
+pub const TWEPOCH: u64 = 1288834974657;
+
+/// Bit layout of a Snowflake ID, from the most significant bits: timestamp, datacenter ID,
+/// worker ID and sequence number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// The epoch of the timestamp part, in Unix milliseconds.
+    pub epoch_ms: u64,
+    pub datacenter_id_bits: u32,
+    pub worker_id_bits: u32,
+    pub sequence_bits: u32,
+}
+
+/// A port of `IdWorker` of Twitter's Snowflake as of 2010.
+///
+/// <https://github.com/twitter-archive/snowflake/blob/snowflake-2010/src/main/scala/com/twitter/service/snowflake/IdWorker.scala>
+#[derive(Debug)]
+pub struct IdWorker<C = SystemClock> {
+    layout: Layout,
+    datacenter_id: u64,
+    worker_id: u64,
+    sequence: u64,
+    last_timestamp_ms: Option<u64>,
+    clock: C,
+}
+
+/// A source of the current time in Unix milliseconds.
+pub trait Clock {
+    fn now_ms(&mut self) -> u64;
+}
+
+/// A `Clock` backed by `SystemTime::now`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The worker ID does not fit in the layout.
+    InvalidWorkerId(u64),
+    /// The datacenter ID does not fit in the layout.
+    InvalidDatacenterId(u64),
+    /// The clock has moved backwards from the timestamp of the last generated ID.
+    ClockMovedBackwards {
+        last_timestamp_ms: u64,
+        timestamp_ms: u64,
+    },
+    /// The timestamp is before the epoch or does not fit in the layout.
+    TimestampOutOfRange(u64),
+    /// All the sequence numbers in the millisecond have been used up.
+    SequenceExhausted,
+}
+
+impl Layout {
+    /// The layout of Twitter's Snowflake IDs.
+    pub const TWITTER: Layout = Layout {
+        epoch_ms: TWEPOCH,
+        datacenter_id_bits: 5,
+        worker_id_bits: 5,
+        sequence_bits: 12,
+    };
+
+    /// The layout of Discord's Snowflake IDs, whose internal worker ID and process ID
+    /// correspond to the datacenter ID and the worker ID respectively.
+    pub const DISCORD: Layout = Layout {
+        epoch_ms: 1420070400000,
+        ..Layout::TWITTER
+    };
+
+    /// The layout of Mastodon's status IDs, which consist of a Unix timestamp and 16 bits of
+    /// sequence data without datacenter or worker IDs.
+    pub const MASTODON: Layout = Layout {
+        epoch_ms: 0,
+        datacenter_id_bits: 0,
+        worker_id_bits: 0,
+        sequence_bits: 16,
+    };
+
+    pub const fn timestamp_shift(&self) -> u32 {
+        self.datacenter_id_bits + self.worker_id_bits + self.sequence_bits
+    }
+
+    pub const fn datacenter_id_shift(&self) -> u32 {
+        self.worker_id_bits + self.sequence_bits
+    }
+
+    pub const fn worker_id_shift(&self) -> u32 {
+        self.sequence_bits
+    }
+
+    pub const fn max_datacenter_id(&self) -> u64 {
+        mask(self.datacenter_id_bits)
+    }
+
+    pub const fn max_worker_id(&self) -> u64 {
+        mask(self.worker_id_bits)
+    }
+
+    pub const fn max_sequence(&self) -> u64 {
+        mask(self.sequence_bits)
+    }
+
+    /// Returns the timestamp of the ID in Unix milliseconds.
+    pub const fn timestamp_ms(&self, id: u64) -> u64 {
+        (id >> self.timestamp_shift()) + self.epoch_ms
+    }
+
+    /// Returns the timestamp of the ID in Unix milliseconds, or `None` if it overflows, e.g.
+    /// with a custom `epoch_ms`.
+    pub const fn checked_timestamp_ms(&self, id: u64) -> Option<u64> {
+        (id >> self.timestamp_shift()).checked_add(self.epoch_ms)
+    }
+
+    pub const fn datacenter_id(&self, id: u64) -> u64 {
+        (id >> self.datacenter_id_shift()) & self.max_datacenter_id()
+    }
+
+    pub const fn worker_id(&self, id: u64) -> u64 {
+        (id >> self.worker_id_shift()) & self.max_worker_id()
+    }
+
+    pub const fn sequence(&self, id: u64) -> u64 {
+        id & self.max_sequence()
+    }
+
+    /// Composes an ID from its parts, or returns `None` if any of the parts is out of range.
+    pub fn compose(
+        &self,
+        timestamp_ms: u64,
+        datacenter_id: u64,
+        worker_id: u64,
+        sequence: u64,
+    ) -> Option<u64> {
+        if datacenter_id > self.max_datacenter_id()
+            || worker_id > self.max_worker_id()
+            || sequence > self.max_sequence()
+        {
+            return None;
+        }
+        let timestamp = timestamp_ms.checked_sub(self.epoch_ms)?;
+        if timestamp.leading_zeros() < self.timestamp_shift() {
+            return None;
+        }
+        Some(
+            timestamp << self.timestamp_shift()
+                | datacenter_id << self.datacenter_id_shift()
+                | worker_id << self.worker_id_shift()
+                | sequence,
+        )
+    }
+
+    /// Returns the lowest ID with the given timestamp.
+    pub fn min_id_at(&self, timestamp_ms: u64) -> Option<u64> {
+        self.compose(timestamp_ms, 0, 0, 0)
+    }
+
+    /// Returns the highest ID with the given timestamp.
+    pub fn max_id_at(&self, timestamp_ms: u64) -> Option<u64> {
+        self.compose(
+            timestamp_ms,
+            self.max_datacenter_id(),
+            self.max_worker_id(),
+            self.max_sequence(),
+        )
+    }
+}
+
+impl IdWorker {
+    pub fn new(datacenter_id: u64, worker_id: u64) -> Result<Self, Error> {
+        Self::with_clock(Layout::TWITTER, datacenter_id, worker_id, SystemClock)
+    }
+}
+
+impl<C: Clock> IdWorker<C> {
+    pub fn with_clock(
+        layout: Layout,
+        datacenter_id: u64,
+        worker_id: u64,
+        clock: C,
+    ) -> Result<Self, Error> {
+        if worker_id > layout.max_worker_id() {
+            return Err(Error::InvalidWorkerId(worker_id));
+        }
+        if datacenter_id > layout.max_datacenter_id() {
+            return Err(Error::InvalidDatacenterId(datacenter_id));
+        }
+        Ok(IdWorker {
+            layout,
+            datacenter_id,
+            worker_id,
+            sequence: 0,
+            last_timestamp_ms: None,
+            clock,
+        })
+    }
+
+    /// Generates a new ID, waiting for the next millisecond if the sequence numbers of
+    /// the current millisecond are used up.
+    pub fn next_id(&mut self) -> Result<u64, Error> {
+        loop {
+            let timestamp_ms = self.clock.now_ms();
+            match self.next_id_at(timestamp_ms) {
+                Err(Error::SequenceExhausted) => std::hint::spin_loop(),
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<C> IdWorker<C> {
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn datacenter_id(&self) -> u64 {
+        self.datacenter_id
+    }
+
+    pub fn worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
+    /// Returns the timestamp of the last generated ID, if any.
+    pub fn last_timestamp_ms(&self) -> Option<u64> {
+        self.last_timestamp_ms
+    }
+
+    /// Generates a new ID as if the clock reads `timestamp_ms`.
+    ///
+    /// Unlike `next_id`, this returns `Error::SequenceExhausted` instead of waiting for
+    /// the next millisecond, leaving the state of the worker intact.
+    pub fn next_id_at(&mut self, timestamp_ms: u64) -> Result<u64, Error> {
+        let sequence = match self.last_timestamp_ms {
+            Some(last_timestamp_ms) if timestamp_ms < last_timestamp_ms => {
+                return Err(Error::ClockMovedBackwards {
+                    last_timestamp_ms,
+                    timestamp_ms,
+                });
+            }
+            Some(last_timestamp_ms) if timestamp_ms == last_timestamp_ms => {
+                let sequence = (self.sequence + 1) & self.layout.max_sequence();
+                if sequence == 0 {
+                    return Err(Error::SequenceExhausted);
+                }
+                sequence
+            }
+            _ => 0,
+        };
+
+        let id = self
+            .layout
+            .compose(timestamp_ms, self.datacenter_id, self.worker_id, sequence)
+            .ok_or(Error::TimestampOutOfRange(timestamp_ms))?;
+        self.sequence = sequence;
+        self.last_timestamp_ms = Some(timestamp_ms);
+
+        Ok(id)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now_ms(&mut self) -> u64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+}
+
+impl<F: FnMut() -> u64> Clock for F {
+    fn now_ms(&mut self) -> u64 {
+        self()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::InvalidWorkerId(id) => write!(f, "worker ID out of range: {}", id),
+            Error::InvalidDatacenterId(id) => write!(f, "datacenter ID out of range: {}", id),
+            Error::ClockMovedBackwards {
+                last_timestamp_ms,
+                timestamp_ms,
+            } => write!(
+                f,
+                "clock is moving backwards; rejecting requests for {} ms",
+                last_timestamp_ms - timestamp_ms
+            ),
+            Error::TimestampOutOfRange(timestamp_ms) => {
+                write!(f, "timestamp out of range: {}", timestamp_ms)
+            }
+            Error::SequenceExhausted => f.write_str("sequence exhausted in the millisecond"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+const fn mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The timestamp of the ID of the tweet 1050118621198921728.
+    const TIMESTAMP_MS: u64 = 1539202764211;
+
+    #[test]
+    fn known_id() {
+        let mut worker = IdWorker::with_clock(Layout::TWITTER, 10, 27, || TIMESTAMP_MS).unwrap();
+        let id = worker.next_id().unwrap();
+        assert_eq!(id, 1050118621198921728);
+        assert_eq!(Layout::TWITTER.timestamp_ms(id), TIMESTAMP_MS);
+        assert_eq!(Layout::TWITTER.datacenter_id(id), 10);
+        assert_eq!(Layout::TWITTER.worker_id(id), 27);
+        assert_eq!(Layout::TWITTER.sequence(id), 0);
+    }
+
+    #[test]
+    fn sequence_rollover() {
+        // The clock stays in a millisecond for more calls than there are sequence numbers.
+        let mut calls = 0;
+        let clock = move || {
+            calls += 1;
+            if calls <= 5000 {
+                TIMESTAMP_MS
+            } else {
+                TIMESTAMP_MS + 1
+            }
+        };
+        let mut worker = IdWorker::with_clock(Layout::TWITTER, 1, 2, clock).unwrap();
+        for sequence in 0..=Layout::TWITTER.max_sequence() {
+            let id = worker.next_id().unwrap();
+            assert_eq!(Layout::TWITTER.timestamp_ms(id), TIMESTAMP_MS);
+            assert_eq!(Layout::TWITTER.sequence(id), sequence);
+        }
+        assert_eq!(
+            worker.next_id_at(TIMESTAMP_MS),
+            Err(Error::SequenceExhausted)
+        );
+
+        // `next_id` waits for the next millisecond and starts a fresh sequence.
+        let id = worker.next_id().unwrap();
+        assert_eq!(Layout::TWITTER.timestamp_ms(id), TIMESTAMP_MS + 1);
+        assert_eq!(Layout::TWITTER.sequence(id), 0);
+        assert_eq!(worker.last_timestamp_ms(), Some(TIMESTAMP_MS + 1));
+    }
+
+    #[test]
+    fn clock_moved_backwards() {
+        let mut worker = IdWorker::with_clock(Layout::TWITTER, 1, 2, || TIMESTAMP_MS).unwrap();
+        worker.next_id().unwrap();
+        assert_eq!(
+            worker.next_id_at(TIMESTAMP_MS - 5),
+            Err(Error::ClockMovedBackwards {
+                last_timestamp_ms: TIMESTAMP_MS,
+                timestamp_ms: TIMESTAMP_MS - 5,
+            })
+        );
+        // The refusal leaves the worker intact.
+        let id = worker.next_id().unwrap();
+        assert_eq!(Layout::TWITTER.sequence(id), 1);
+    }
+
+    #[test]
+    fn invalid_ids() {
+        let clock = || TIMESTAMP_MS;
+        assert_eq!(
+            IdWorker::with_clock(Layout::TWITTER, 31, 32, clock).err(),
+            Some(Error::InvalidWorkerId(32))
+        );
+        assert_eq!(
+            IdWorker::with_clock(Layout::TWITTER, 32, 31, clock).err(),
+            Some(Error::InvalidDatacenterId(32))
+        );
+        assert!(IdWorker::with_clock(Layout::TWITTER, 31, 31, clock).is_ok());
+    }
+}
@@ -0,0 +1,328 @@
+//! Identifier schemes whose most significant bits hold the creation time.
+//!
+//! Such IDs are only k-sorted when generated on multiple machines, so the `since_id` math of
+//! `SinceIdPolicy` applies to any of them through the `TimePrefixed` trait.
+
+use std::fmt::Debug;
+
+use crate::snowflake::Layout;
+
+/// A scheme of time-prefixed identifiers.
+pub trait TimePrefixed {
+    /// The ID as an integer (or a byte string) whose order agrees with the order of the string
+    /// representation.
+    type Id: Copy + Debug + Ord;
+
+    /// Returns the timestamp of the ID in Unix milliseconds, truncated to the precision of
+    /// the scheme.
+    fn timestamp_ms(&self, id: Self::Id) -> u64;
+
+    /// Returns the highest ID that is lower than any ID created at or after `timestamp_ms`,
+    /// saturating at the lowest and the highest ID of the scheme.
+    fn id_before(&self, timestamp_ms: u64) -> Self::Id;
+
+    fn format(&self, id: Self::Id) -> String;
+
+    fn parse(&self, s: &str) -> Option<Self::Id>;
+}
+
+/// Universally Unique Lexicographically Sortable Identifier.
+///
+/// <https://github.com/ulid/spec>
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ulid;
+
+/// UUID version 7 of RFC 9562.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidV7;
+
+/// K-Sortable Unique IDentifier of Segment.
+///
+/// <https://github.com/segmentio/ksuid>
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ksuid;
+
+/// Length of the timestamp part of ULIDs and UUIDv7s in bits.
+const UNIX_TS_MS_BITS: u32 = 48;
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Number of characters in the string representation of a ULID.
+const ULID_LEN: usize = 26;
+
+/// The epoch of the timestamp part of KSUIDs in Unix seconds.
+const KSUID_EPOCH: u64 = 1400000000;
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// Number of characters in the string representation of a KSUID.
+const KSUID_LEN: usize = 27;
+
+impl TimePrefixed for Layout {
+    type Id = u64;
+
+    fn timestamp_ms(&self, id: u64) -> u64 {
+        Layout::timestamp_ms(self, id)
+    }
+
+    fn id_before(&self, timestamp_ms: u64) -> u64 {
+        match self.min_id_at(timestamp_ms) {
+            Some(id) => id.saturating_sub(1),
+            None if timestamp_ms < self.epoch_ms => 0,
+            None => u64::MAX,
+        }
+    }
+
+    fn format(&self, id: u64) -> String {
+        id.to_string()
+    }
+
+    fn parse(&self, s: &str) -> Option<u64> {
+        s.parse().ok()
+    }
+}
+
+impl TimePrefixed for Ulid {
+    type Id = u128;
+
+    fn timestamp_ms(&self, id: u128) -> u64 {
+        (id >> (128 - UNIX_TS_MS_BITS)) as u64
+    }
+
+    fn id_before(&self, timestamp_ms: u64) -> u128 {
+        if timestamp_ms >> UNIX_TS_MS_BITS != 0 {
+            return u128::MAX;
+        }
+        ((timestamp_ms as u128) << (128 - UNIX_TS_MS_BITS)).saturating_sub(1)
+    }
+
+    fn format(&self, id: u128) -> String {
+        (0..ULID_LEN)
+            .map(|i| {
+                let shift = 5 * (ULID_LEN - 1 - i);
+                CROCKFORD[(id >> shift) as usize & 0x1F] as char
+            })
+            .collect()
+    }
+
+    fn parse(&self, s: &str) -> Option<u128> {
+        if s.len() != ULID_LEN {
+            return None;
+        }
+        s.bytes().try_fold(0u128, |id, b| {
+            let b = b.to_ascii_uppercase();
+            let digit = CROCKFORD.iter().position(|&c| c == b)?;
+            // Overflows if the first character is greater than `7`.
+            id.checked_mul(32)?.checked_add(digit as u128)
+        })
+    }
+}
+
+impl TimePrefixed for UuidV7 {
+    type Id = u128;
+
+    fn timestamp_ms(&self, id: u128) -> u64 {
+        (id >> (128 - UNIX_TS_MS_BITS)) as u64
+    }
+
+    /// Returns the highest UUIDv7 of the preceding millisecond, which is a valid UUIDv7 unlike
+    /// the lowest UUIDv7 of the millisecond minus one.
+    fn id_before(&self, timestamp_ms: u64) -> u128 {
+        if timestamp_ms == 0 {
+            return 0;
+        }
+        if timestamp_ms >> UNIX_TS_MS_BITS != 0 {
+            return u128::MAX;
+        }
+        let version = 0x7 << 76;
+        let rand_a = 0xFFF << 64;
+        let variant = 0b10 << 62;
+        let rand_b = (1 << 62) - 1;
+        ((timestamp_ms as u128 - 1) << (128 - UNIX_TS_MS_BITS))
+            | version
+            | rand_a
+            | variant
+            | rand_b
+    }
+
+    fn format(&self, id: u128) -> String {
+        let hex = format!("{:032x}", id);
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+
+    fn parse(&self, s: &str) -> Option<u128> {
+        let hex: String = s.chars().filter(|&c| c != '-').collect();
+        if hex.len() != 32 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u128::from_str_radix(&hex, 16).ok()
+    }
+}
+
+impl TimePrefixed for Ksuid {
+    type Id = [u8; 20];
+
+    fn timestamp_ms(&self, id: [u8; 20]) -> u64 {
+        let timestamp = u32::from_be_bytes([id[0], id[1], id[2], id[3]]);
+        (timestamp as u64 + KSUID_EPOCH) * 1000
+    }
+
+    /// Returns the highest KSUID of the second preceding the one of `timestamp_ms`, since
+    /// the timestamps of KSUIDs are in seconds.
+    fn id_before(&self, timestamp_ms: u64) -> [u8; 20] {
+        let Some(timestamp) = (timestamp_ms / 1000).checked_sub(KSUID_EPOCH + 1) else {
+            return [0; 20];
+        };
+        let Ok(timestamp) = u32::try_from(timestamp) else {
+            return [0xFF; 20];
+        };
+        let mut ret = [0xFF; 20];
+        ret[..4].copy_from_slice(&timestamp.to_be_bytes());
+        ret
+    }
+
+    fn format(&self, id: [u8; 20]) -> String {
+        let mut ret = [b'0'; KSUID_LEN];
+        let mut n = id;
+        for digit in ret.iter_mut().rev() {
+            // Long division of the big-endian number by 62.
+            let mut rem = 0u32;
+            for byte in &mut n {
+                let acc = rem << 8 | *byte as u32;
+                *byte = (acc / 62) as u8;
+                rem = acc % 62;
+            }
+            *digit = BASE62[rem as usize];
+        }
+        String::from_utf8(ret.to_vec()).unwrap()
+    }
+
+    fn parse(&self, s: &str) -> Option<[u8; 20]> {
+        if s.len() != KSUID_LEN {
+            return None;
+        }
+        let mut ret = [0u8; 20];
+        for b in s.bytes() {
+            let mut carry = BASE62.iter().position(|&c| c == b)? as u32;
+            for byte in ret.iter_mut().rev() {
+                let acc = *byte as u32 * 62 + carry;
+                *byte = acc as u8;
+                carry = acc >> 8;
+            }
+            if carry != 0 {
+                return None;
+            }
+        }
+        Some(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::poller::SinceIdPolicy;
+
+    use super::*;
+
+    /// The example of the ULID spec.
+    const ULID: &str = "01ARZ3NDEKTSV4RRFFQ69G5FAV";
+    const ULID_MS: u64 = 1469922850259;
+    /// The example of Appendix A.6 of RFC 9562.
+    const UUID_V7: &str = "017f22e2-79b0-7cc3-98c4-dc0c0c07398f";
+    const UUID_V7_MS: u64 = 1645557742000;
+    /// The example of the README of `segmentio/ksuid`.
+    const KSUID: &str = "0ujtsYcgvSTl8PAuAdqWYSMnLOv";
+    const KSUID_MS: u64 = (107608047 + KSUID_EPOCH) * 1000;
+
+    fn assert_round_trip<S: TimePrefixed>(scheme: &S, s: &str, timestamp_ms: u64) -> S::Id {
+        let id = scheme.parse(s).unwrap();
+        assert_eq!(scheme.format(id), s);
+        assert_eq!(scheme.timestamp_ms(id), timestamp_ms);
+        id
+    }
+
+    /// Asserts that `since_id` stays between its lower bound and `latest_id` at any time.
+    fn assert_clamped<S: TimePrefixed>(scheme: &S, latest_id: S::Id) {
+        let policy = SinceIdPolicy::Adjusted { k_ms: 1000 };
+        let lower = policy.lower(scheme, latest_id);
+        assert!(lower <= latest_id);
+        let latest_ms = scheme.timestamp_ms(latest_id);
+        for retrieved_ms in [0, latest_ms - 60000, latest_ms, latest_ms + 1500, u64::MAX] {
+            let since_id = policy.since_id(scheme, latest_id, retrieved_ms);
+            assert!(
+                lower <= since_id && since_id <= latest_id,
+                "{}",
+                retrieved_ms
+            );
+        }
+        assert_eq!(policy.since_id(scheme, latest_id, u64::MAX), latest_id);
+        assert_eq!(policy.since_id(scheme, latest_id, 0), lower);
+    }
+
+    #[test]
+    fn ulid() {
+        let id = assert_round_trip(&Ulid, ULID, ULID_MS);
+        assert_eq!(Ulid.parse(&ULID.to_lowercase()), Some(id));
+        assert_eq!(Ulid.parse("7ZZZZZZZZZZZZZZZZZZZZZZZZZ"), Some(u128::MAX));
+        // The first character above `7` would overflow 128 bits.
+        assert_eq!(Ulid.parse("80000000000000000000000000"), None);
+        assert_eq!(Ulid.parse(&ULID[1..]), None);
+
+        let before = Ulid.id_before(ULID_MS);
+        assert_eq!(Ulid.timestamp_ms(before), ULID_MS - 1);
+        assert_eq!(
+            before + 1,
+            Ulid.parse("01ARZ3NDEK0000000000000000").unwrap()
+        );
+        assert_clamped(&Ulid, id);
+    }
+
+    #[test]
+    fn uuid_v7() {
+        let id = assert_round_trip(&UuidV7, UUID_V7, UUID_V7_MS);
+        assert_eq!(UuidV7.parse(&UUID_V7.to_uppercase()), Some(id));
+        assert_eq!(UuidV7.parse(&UUID_V7[1..]), None);
+
+        let before = UuidV7.id_before(UUID_V7_MS);
+        assert_eq!(UuidV7.timestamp_ms(before), UUID_V7_MS - 1);
+        assert_eq!(before >> 76 & 0xF, 0x7, "version");
+        assert_eq!(before >> 62 & 0b11, 0b10, "variant");
+        assert!(before < id);
+        assert_clamped(&UuidV7, id);
+    }
+
+    #[test]
+    fn ksuid() {
+        let id = assert_round_trip(&Ksuid, KSUID, KSUID_MS);
+        assert_eq!(
+            id[4..],
+            [
+                0xB5, 0xA1, 0xCD, 0x34, 0xB5, 0xF9, 0x9D, 0x11, 0x54, 0xFB, 0x68, 0x53, 0x34, 0x5C,
+                0x97, 0x35
+            ]
+        );
+        assert_eq!(Ksuid.parse(&KSUID[1..]), None);
+        // Above the maximum KSUID, `aWgEPTl1tmebfsQzFP4bxwgy80V`.
+        assert_eq!(Ksuid.parse("aWgEPTl1tmebfsQzFP4bxwgy80W"), None);
+
+        // The maximum KSUID of the preceding second, for any millisecond in the second.
+        let mut expected = [0xFF; 20];
+        expected[..4].copy_from_slice(&107608046u32.to_be_bytes());
+        assert_eq!(Ksuid.id_before(KSUID_MS), expected);
+        assert_eq!(Ksuid.id_before(KSUID_MS + 999), expected);
+        assert!(Ksuid.id_before(KSUID_MS + 1000) > id);
+        assert_clamped(&Ksuid, id);
+    }
+
+    #[test]
+    fn snowflake() {
+        let id = assert_round_trip(&Layout::TWITTER, "1050118621198921728", 1539202764211);
+        assert_eq!(
+            Layout::TWITTER.id_before(1539202764211),
+            (id >> 22 << 22) - 1
+        );
+        assert_clamped(&Layout::TWITTER, id);
+    }
+}
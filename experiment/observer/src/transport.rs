@@ -0,0 +1,151 @@
+//! Transports that carry the API requests and their responses.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::future::{self, BoxFuture, FutureExt};
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::client::conn::{http1, http2};
+use hyper::{Request, Response};
+use tokio::sync::Mutex;
+
+use crate::util;
+
+/// The body of a response, boxed so that every transport can deliver it in its own way.
+pub type Body = UnsyncBoxBody<Bytes, anyhow::Error>;
+
+/// A future of a response to a request sent by a `Transport`.
+pub type SendFuture = Pin<Box<dyn Future<Output = anyhow::Result<Response<Body>>> + Send>>;
+
+/// A way to send the API requests, e.g. a connection to the API host.
+///
+/// The errors in the connections are reported as `hyper::Error`, after which the connection
+/// should be re-established with `reconnect`.
+pub trait Transport: Send {
+    fn send(&mut self, request: Request<Empty<Bytes>>) -> SendFuture;
+
+    /// Re-establishes the connection after an error in it.
+    fn reconnect(&mut self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+/// An HTTP/2 connection to a host over TLS.
+pub struct Http2 {
+    host: String,
+    port: u16,
+    sender: http2::SendRequest<Empty<Bytes>>,
+}
+
+/// An HTTP/1.1 connection to a host over TLS.
+pub struct Http1 {
+    host: String,
+    port: u16,
+    /// Shared with the futures of the requests, which wait for the connection to be ready.
+    sender: Arc<Mutex<http1::SendRequest<Empty<Bytes>>>>,
+}
+
+/// An in-process transport that answers the requests with a closure instead of sending them,
+/// e.g. to drive the pollers in tests.
+pub struct Mock<F> {
+    handler: F,
+}
+
+/// Connects to `host` over HTTP/1.1 if `http1` is `true`, or over HTTP/2 otherwise.
+pub async fn connect(host: &str, port: u16, http1: bool) -> anyhow::Result<Box<dyn Transport>> {
+    if http1 {
+        Ok(Box::new(Http1::connect(host, port).await?))
+    } else {
+        Ok(Box::new(Http2::connect(host, port).await?))
+    }
+}
+
+impl Http2 {
+    pub async fn connect(host: &str, port: u16) -> anyhow::Result<Self> {
+        Ok(Http2 {
+            host: host.to_owned(),
+            port,
+            sender: util::http2_connect(host, port).await?,
+        })
+    }
+}
+
+impl Transport for Http2 {
+    fn send(&mut self, request: Request<Empty<Bytes>>) -> SendFuture {
+        Box::pin(self.sender.send_request(request).map(box_response))
+    }
+
+    fn reconnect(&mut self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.sender = util::http2_connect(&self.host, self.port).await?;
+            Ok(())
+        })
+    }
+}
+
+impl Http1 {
+    pub async fn connect(host: &str, port: u16) -> anyhow::Result<Self> {
+        Ok(Http1 {
+            host: host.to_owned(),
+            port,
+            sender: Arc::new(Mutex::new(util::http1_connect(host, port).await?)),
+        })
+    }
+}
+
+impl Transport for Http1 {
+    fn send(&mut self, request: Request<Empty<Bytes>>) -> SendFuture {
+        let sender = self.sender.clone();
+        Box::pin(async move {
+            let mut sender = sender.lock().await;
+            // The connection takes the next request only after the previous response is done.
+            sender.ready().await?;
+            let response = sender.send_request(request);
+            drop(sender);
+            box_response(response.await)
+        })
+    }
+
+    fn reconnect(&mut self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let sender = util::http1_connect(&self.host, self.port).await?;
+            *self.sender.lock().await = sender;
+            Ok(())
+        })
+    }
+}
+
+impl<F> Mock<F>
+where
+    F: FnMut(&Request<Empty<Bytes>>) -> anyhow::Result<Response<Bytes>> + Send,
+{
+    pub fn new(handler: F) -> Self {
+        Mock { handler }
+    }
+}
+
+impl<F> Transport for Mock<F>
+where
+    F: FnMut(&Request<Empty<Bytes>>) -> anyhow::Result<Response<Bytes>> + Send,
+{
+    fn send(&mut self, request: Request<Empty<Bytes>>) -> SendFuture {
+        let response = (self.handler)(&request).map(|response| {
+            response.map(|body| {
+                Full::new(body)
+                    .map_err(|never| match never {})
+                    .boxed_unsync()
+            })
+        });
+        Box::pin(future::ready(response))
+    }
+}
+
+fn box_response(
+    result: hyper::Result<Response<hyper::body::Incoming>>,
+) -> anyhow::Result<Response<Body>> {
+    let response = result?;
+    Ok(response.map(|body| body.map_err(anyhow::Error::from).boxed_unsync()))
+}
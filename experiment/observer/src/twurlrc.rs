@@ -6,12 +6,16 @@ use serde::Deserialize;
 pub struct DefaultProfile {
     pub username: String,
     pub token: oauth::Token,
+    /// The bearer token of the default app, if any.
+    pub bearer: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct Twurlrc {
     profiles: HashMap<String, HashMap<String, Profile>>,
     configuration: Configuration,
+    #[serde(default)]
+    bearer_tokens: HashMap<String, String>,
 }
 
 #[derive(Deserialize)]
@@ -36,6 +40,7 @@ impl<'de> Deserialize<'de> for DefaultProfile {
                 Configuration {
                     default_profile: (username, consumer),
                 },
+            mut bearer_tokens,
         } = Twurlrc::deserialize(d)?;
         let mut consumers = if let Some(consumers) = profiles.remove(&username) {
             consumers
@@ -55,8 +60,13 @@ impl<'de> Deserialize<'de> for DefaultProfile {
         };
 
         let username = p.username;
+        let bearer = bearer_tokens.remove(&p.consumer_key);
         let token = oauth::Token::from_parts(p.consumer_key, p.consumer_secret, p.token, p.secret);
 
-        Ok(DefaultProfile { username, token })
+        Ok(DefaultProfile {
+            username,
+            token,
+            bearer,
+        })
     }
 }
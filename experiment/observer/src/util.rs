@@ -1,12 +1,10 @@
 mod tokiort;
 
-use std::cmp::Ordering;
 use std::fmt;
 use std::time::{Duration, SystemTime};
 
-use bytes::Bytes;
+use crate::snowflake::TWEPOCH;
 use futures_util::FutureExt;
-use http_body_util::Empty;
 use hyper::header::HeaderValue;
 use serde::de;
 
@@ -18,8 +16,6 @@ pub const USER_AGENT: HeaderValue = HeaderValue::from_static(concat!(
     env!("CARGO_PKG_VERSION")
 ));
 
-const TWEPOCH: u64 = 1288834974657;
-
 const CLOCK_TOO_EARLY: &str = r#"\
 Greetings from 2023! Unfortunately, your system clock is living an era before the birth of Twitter, \
 a social media service of our era, which this program is targeted at"#;
@@ -30,22 +26,6 @@ a social media service of our era, which this program is targeted at"#;
 /// A `DeserializeSeed` implementation that reuses the given vector to deserialize a sequence.
 pub struct DeserializeIntoVec<'a, T>(pub &'a mut Vec<T>);
 
-/// Polyfill for the unstable `<[T]>::is_sorted_by` method.
-///
-/// <https://github.com/rust-lang/rust/issues/53485>
-pub trait SliceIsSortedExt<T> {
-    fn is_sorted_by<F: FnMut(&T, &T) -> Option<Ordering>>(&self, f: F) -> bool;
-}
-
-impl<T> SliceIsSortedExt<T> for [T] {
-    fn is_sorted_by<F: FnMut(&T, &T) -> Option<Ordering>>(&self, mut f: F) -> bool {
-        self.windows(2).all(|w| {
-            let (u, t) = (&w[1], &w[0]);
-            f(t, u).map_or(false, Ordering::is_le)
-        })
-    }
-}
-
 impl<'de, 'a, T> de::DeserializeSeed<'de> for DeserializeIntoVec<'a, T>
 where
     T: de::Deserialize<'de>,
@@ -81,38 +61,16 @@ where
     }
 }
 
-/// Returns the first element of the set difference `x - y`,
-/// assuming that `x` and `y` is sorted by the ordering given by `comparator`.
-pub fn first_diff_sorted_by<T>(
-    x: impl IntoIterator<Item = T>,
-    y: impl IntoIterator<Item = T>,
-    mut comparator: impl FnMut(&T, &T) -> Ordering,
-) -> Option<T> {
-    let mut y = y.into_iter();
-    'outer: for t in x {
-        for u in &mut y {
-            match comparator(&t, &u) {
-                Ordering::Greater => {}
-                Ordering::Equal => continue 'outer,
-                Ordering::Less => return Some(t),
-            }
-        }
-        return Some(t);
-    }
-
-    None
-}
-
-pub async fn http2_connect(
+pub async fn http2_connect<B>(
     host: &str,
     port: u16,
-) -> anyhow::Result<hyper::client::conn::http2::SendRequest<Empty<Bytes>>> {
-    let stream = tokio::net::TcpStream::connect((host, port)).await?;
-    let tls_connector: tokio_native_tls::TlsConnector =
-        tokio_native_tls::native_tls::TlsConnector::new()
-            .expect("Error initializing TLS connector")
-            .into();
-    let stream = tls_connector.connect(host, stream).await?;
+) -> anyhow::Result<hyper::client::conn::http2::SendRequest<B>>
+where
+    B: hyper::body::Body + Send + Unpin + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let stream = tls_connect(host, port).await?;
     let (ret, conn) = hyper::client::conn::http2::Builder::new()
         .executor(TokioExecutor)
         .handshake(stream)
@@ -127,6 +85,39 @@ pub async fn http2_connect(
     Ok(ret)
 }
 
+pub async fn http1_connect<B>(
+    host: &str,
+    port: u16,
+) -> anyhow::Result<hyper::client::conn::http1::SendRequest<B>>
+where
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let stream = tls_connect(host, port).await?;
+    let (ret, conn) = hyper::client::conn::http1::handshake(stream).await?;
+
+    tokio::spawn(conn.map(|result| {
+        if let Err(e) = result {
+            tracing::error!("Error in HTTP connection: {}", e);
+        }
+    }));
+
+    Ok(ret)
+}
+
+async fn tls_connect(
+    host: &str,
+    port: u16,
+) -> anyhow::Result<tokio_native_tls::TlsStream<tokio::net::TcpStream>> {
+    let stream = tokio::net::TcpStream::connect((host, port)).await?;
+    let tls_connector: tokio_native_tls::TlsConnector =
+        tokio_native_tls::native_tls::TlsConnector::new()
+            .expect("Error initializing TLS connector")
+            .into();
+    Ok(tls_connector.connect(host, stream).await?)
+}
+
 pub fn time_to_unix(time: SystemTime) -> Duration {
     time.duration_since(SystemTime::UNIX_EPOCH)
         .expect(CLOCK_TOO_EARLY)
@@ -147,3 +138,238 @@ pub fn unix_ms_to_sf(unix_ms: u64) -> u64 {
         .checked_shl(22)
         .expect(CLOCK_TOO_LATE)
 }
+
+pub fn sf_to_unix_ms(id: u64) -> u64 {
+    (id >> 22) + TWEPOCH
+}
+
+/// Formats the Unix time as an ISO 8601 date-time in UTC with millisecond precision.
+pub fn format_unix_ms(unix_ms: u64) -> String {
+    let secs = unix_ms / 1000;
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        unix_ms % 1000,
+    )
+}
+
+/// Parses an ISO 8601 date-time in UTC like `2023-01-01T00:00:00.000Z` into Unix time in
+/// milliseconds. The fractional seconds are optional and truncated to milliseconds, and the
+/// offset may also be written as `+00:00`.
+pub fn parse_unix_ms(s: &str) -> Option<u64> {
+    fn num(s: &str) -> Option<u64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+
+    let s = s
+        .strip_suffix('Z')
+        .or_else(|| s.strip_suffix('z'))
+        .or_else(|| s.strip_suffix("+00:00"))?;
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+
+    let mut date = date.splitn(3, '-');
+    let year = num(date.next()?)?;
+    let month = num(date.next()?)?;
+    let day = num(date.next()?)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (time, millis) = match time.split_once('.') {
+        Some((time, frac)) => {
+            num(frac)?;
+            let frac = &frac[..frac.len().min(3)];
+            (time, num(frac)? * 10u64.pow(3 - frac.len() as u32))
+        }
+        None => (time, 0),
+    };
+    let mut time = time.splitn(3, ':');
+    let hour = num(time.next()?)?;
+    let minute = num(time.next()?)?;
+    let second = num(time.next()?)?;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days = days_from_civil(i64::try_from(year).ok()?, month, day)?;
+    u64::try_from(days)
+        .ok()?
+        .checked_mul(86400)?
+        .checked_add((hour * 60 + minute) * 60 + second)?
+        .checked_mul(1000)?
+        .checked_add(millis)
+}
+
+/// Parses a time in the format of `created_at` of the Twitter API v1.1, like
+/// `Wed Oct 10 20:19:24 +0000 2018`, into Unix time in milliseconds.
+pub fn parse_twitter_time(s: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let mut parts = s.split_ascii_whitespace();
+    let _weekday = parts.next()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|&m| m == month)? as u64 + 1;
+    let day: u64 = parts.next()?.parse().ok()?;
+    let time = parts.next()?;
+    let offset = parts.next()?;
+    let year: i64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut time = time.splitn(3, ':').map(|s| s.parse::<u64>().ok());
+    let hour = time.next()??;
+    let minute = time.next()??;
+    let second = time.next()??;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let (sign, offset) = offset.split_at_checked(1)?;
+    if offset.len() != 4 || !offset.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let offset: i64 = offset.parse().ok()?;
+    if offset % 100 > 59 {
+        return None;
+    }
+    let offset_secs = (offset / 100 * 60 + offset % 100) * 60;
+    let offset_secs = match sign {
+        "+" => offset_secs,
+        "-" => -offset_secs,
+        _ => return None,
+    };
+
+    let secs = days_from_civil(year, month, day)?
+        .checked_mul(86400)?
+        .checked_add((hour * 3600 + minute * 60 + second) as i64)?
+        .checked_sub(offset_secs)?;
+    u64::try_from(secs).ok()?.checked_mul(1000)
+}
+
+// The following conversions between days since the Unix epoch and proleptic Gregorian dates
+// are adapted from <https://howardhinnant.github.io/date_algorithms.html>.
+
+/// Returns the date of the day, which cannot overflow for the days of a `u64` Unix time.
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe as i64 + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns the days since the Unix epoch of the date, or `None` if it overflows. The `month`
+/// and `day` must be in `1..=12` and `1..=31` respectively.
+fn days_from_civil(year: i64, month: u64, day: u64) -> Option<i64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400) as u64;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era.checked_mul(146097)?.checked_add(doe as i64 - 719468)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snowflake::Layout;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for s in [
+            "1970-01-01T00:00:00.000Z",
+            "2000-02-29T12:34:56.789Z",
+            "2020-02-29T23:59:59.999Z",
+            "2023-01-01T00:00:00.000Z",
+            "2100-03-01T00:00:00.001Z",
+        ] {
+            let unix_ms = parse_unix_ms(s).unwrap();
+            assert_eq!(format_unix_ms(unix_ms), s);
+        }
+        for unix_ms in [0, 951782400000, 1582934400000, 1539202764211, 4107542400000] {
+            assert_eq!(parse_unix_ms(&format_unix_ms(unix_ms)), Some(unix_ms));
+        }
+    }
+
+    #[test]
+    fn leap_years() {
+        assert_eq!(parse_unix_ms("2000-02-29T00:00:00Z"), Some(951782400000));
+        assert_eq!(parse_unix_ms("2020-02-29T00:00:00Z"), Some(1582934400000));
+        // 1900 and 2100 are not leap years.
+        assert_eq!(
+            format_unix_ms(parse_unix_ms("2100-02-28T00:00:00Z").unwrap() + 86400000),
+            "2100-03-01T00:00:00.000Z",
+        );
+        assert_eq!(format_unix_ms(4107456000000), "2100-02-28T00:00:00.000Z");
+    }
+
+    #[test]
+    fn tweet_ids() {
+        // The example Tweet object in the documentation of the Twitter API v1.1.
+        let id = 1050118621198921728;
+        let created_at = parse_twitter_time("Wed Oct 10 20:19:24 +0000 2018").unwrap();
+        assert_eq!(created_at, 1539202764000);
+        assert_eq!(Layout::TWITTER.timestamp_ms(id), 1539202764211);
+        assert_eq!(sf_to_unix_ms(id) / 1000, created_at / 1000);
+        assert_eq!(
+            format_unix_ms(sf_to_unix_ms(id)),
+            "2018-10-10T20:19:24.211Z"
+        );
+        assert_eq!(
+            parse_twitter_time("Wed Oct 10 22:19:24 +0200 2018"),
+            Some(created_at)
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(parse_unix_ms("99999999999999999999-01-01T00:00:00Z"), None);
+        assert_eq!(parse_unix_ms("9223372036854775807-12-31T23:59:59Z"), None);
+        assert_eq!(parse_unix_ms("2023-01-01T24:00:00Z"), None);
+        assert_eq!(parse_unix_ms("2023-13-01T00:00:00Z"), None);
+        assert_eq!(parse_unix_ms("1969-12-31T23:59:59Z"), None);
+        assert_eq!(
+            parse_twitter_time("Wed Oct 10 20:19:24 +0000 9223372036854775807"),
+            None
+        );
+        assert_eq!(
+            parse_twitter_time("Wed Oct 10 99999999999:19:24 +0000 2018"),
+            None
+        );
+        assert_eq!(
+            parse_twitter_time("Wed Oct 10 20:19:24 +99999999999 2018"),
+            None
+        );
+        let _ = format_unix_ms(u64::MAX);
+
+        let layout = Layout {
+            epoch_ms: u64::MAX,
+            ..Layout::TWITTER
+        };
+        assert_eq!(layout.checked_timestamp_ms(u64::MAX), None);
+    }
+}
@@ -0,0 +1,6 @@
+#[test]
+fn timeline_request() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/timeline-request.rs");
+    t.compile_fail("tests/ui/timeline-request-missing-*.rs");
+}
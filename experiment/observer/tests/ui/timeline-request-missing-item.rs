@@ -0,0 +1,11 @@
+use leaky_snowflake_observer::api::TimelineRequest;
+use serde::Serialize;
+
+#[derive(Serialize, oauth::Request, TimelineRequest)]
+#[timeline(path = "/items.json")]
+struct Items {
+    #[timeline(since_id)]
+    since_id: Option<u64>,
+}
+
+fn main() {}
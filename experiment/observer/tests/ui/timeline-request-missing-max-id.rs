@@ -0,0 +1,15 @@
+use leaky_snowflake_observer::api::{Cursor, TimelineRequest};
+use serde::Serialize;
+
+#[derive(Serialize, oauth::Request, TimelineRequest)]
+#[timeline(path = "/items.json", item = leaky_snowflake_observer::api::Tweet)]
+struct Items {
+    #[timeline(since_id)]
+    since_id: Option<u64>,
+    #[oauth1(skip)]
+    #[serde(skip)]
+    #[timeline(cursor)]
+    cursor: Option<Cursor>,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use leaky_snowflake_observer::api::TimelineRequest;
+use serde::Serialize;
+
+#[derive(Serialize, oauth::Request, TimelineRequest)]
+#[timeline(path = "/items.json", item = leaky_snowflake_observer::api::Tweet)]
+struct Items {
+    since_id: Option<u64>,
+}
+
+fn main() {}
@@ -0,0 +1,41 @@
+use leaky_snowflake_observer::api::{self, Cursor, Endpoint, PagedRequest, RangeRequest, TimelineRequest};
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize, oauth::Request, TimelineRequest)]
+#[timeline(version = "2", path = "/items.json", item = api::Tweet)]
+#[timeline(host = "api.example.com", envelope = "data", oldest_first)]
+struct Items {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[timeline(since_id)]
+    since_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[timeline(max_id)]
+    max_id: Option<u64>,
+    #[oauth1(skip)]
+    #[serde(skip)]
+    #[timeline(cursor)]
+    cursor: Option<Cursor>,
+    #[timeline(limit)]
+    count: u32,
+}
+
+fn main() {
+    assert_eq!(Items::HOST, "api.example.com");
+    assert_eq!(Items::PATH, "/2/items.json");
+    assert_eq!(Items::ENVELOPE, Some("data"));
+    assert!(Items::OLDEST_FIRST);
+
+    let mut items = Items {
+        since_id: None,
+        max_id: None,
+        cursor: None,
+        count: 100,
+    };
+    assert_eq!(items.limit(), 100);
+    items.set_since_id(Some(1));
+    items.set_max_id(Some(2));
+    items.set_cursor(Some(Cursor("3".to_owned())));
+    assert_eq!(items.since_id, Some(1));
+    assert_eq!(items.max_id, Some(2));
+    assert_eq!(items.cursor, Some(Cursor("3".to_owned())));
+}